@@ -0,0 +1,243 @@
+// Legacy campaigns, played with "--campaign <file>": the same map is played game
+// after game, and every game leaves its mark on it. The winner founds a city,
+// builds a bunker or renames a territory, and every later game starts on the
+// map with these changes applied. The campaign file records them in order, along
+// with every win:
+//
+//     win Ann
+//     city Western Australia
+//     win Bo
+//     bunker China
+//     rename Siberia, Bo's Landing
+//
+// Changes name territories as they were called at that point of the campaign,
+// so later lines use the new name of a renamed territory.
+
+use std::fs;
+use std::path::Path;
+
+use petgraph::graph::NodeIndex;
+
+use crate::input::Input;
+use crate::map::Map;
+use crate::{teams, Player};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Win(String),
+    City(String),
+    Bunker(String),
+    Rename(String, String),
+}
+
+pub struct Campaign {
+    pub entries: Vec<Entry>,
+}
+
+impl Campaign {
+    // Loads the campaign file, or starts a new campaign if it does not exist yet.
+    pub fn load(path: &str) -> Result<Campaign, String> {
+        if !Path::new(path).exists() {
+            println!("Starting a new campaign, which will be saved to {}.", path);
+            return Ok(Campaign { entries: Vec::new() });
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read campaign file '{}': {}", path, e))?;
+        Campaign::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Campaign, String> {
+        let mut entries = Vec::new();
+        for (line_index, line) in contents.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (kind, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let argument = argument.trim();
+            if argument.is_empty() {
+                return Err(format!("line {}: '{}' needs a name", line_number, kind));
+            }
+            entries.push(match kind {
+                "win" => Entry::Win(argument.to_string()),
+                "city" => Entry::City(argument.to_string()),
+                "bunker" => Entry::Bunker(argument.to_string()),
+                "rename" => {
+                    let (old_name, new_name) = argument
+                        .split_once(',')
+                        .ok_or_else(|| format!("line {}: expected 'rename <old name>, <new name>'", line_number))?;
+                    Entry::Rename(old_name.trim().to_string(), new_name.trim().to_string())
+                }
+                _ => return Err(format!("line {}: unknown line '{}', expected win, city, bunker or rename", line_number, kind)),
+            });
+        }
+        Ok(Campaign { entries })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            match entry {
+                Entry::Win(name) => text.push_str(&format!("win {}\n", name)),
+                Entry::City(territory_name) => text.push_str(&format!("city {}\n", territory_name)),
+                Entry::Bunker(territory_name) => text.push_str(&format!("bunker {}\n", territory_name)),
+                Entry::Rename(old_name, new_name) => text.push_str(&format!("rename {}, {}\n", old_name, new_name)),
+            }
+        }
+        text
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("could not write campaign file '{}': {}", path, e))
+    }
+
+    // Makes every change of the campaign so far to the map, in order.
+    pub fn apply(&self, map: &mut Map) -> Result<(), String> {
+        for entry in &self.entries {
+            let territory_name = match entry {
+                Entry::Win(_) => continue,
+                Entry::City(territory_name) | Entry::Bunker(territory_name) | Entry::Rename(territory_name, _) => territory_name,
+            };
+            let territory_index = map
+                .find_territory(territory_name)
+                .ok_or_else(|| format!("the campaign changes {}, which is not on the map", territory_name))?;
+            let territory = &mut map.territories[NodeIndex::new(territory_index as usize)];
+            match entry {
+                Entry::Win(_) => {}
+                Entry::City(_) => territory.city = true,
+                Entry::Bunker(_) => territory.bunker = true,
+                Entry::Rename(_, new_name) => territory.name = new_name.clone(),
+            }
+        }
+        Ok(())
+    }
+
+    // Number of games won by each player, most wins first.
+    pub fn wins(&self) -> Vec<(String, u32)> {
+        let mut wins: Vec<(String, u32)> = Vec::new();
+        for entry in &self.entries {
+            if let Entry::Win(name) = entry {
+                match wins.iter_mut().find(|(winner, _)| winner == name) {
+                    Some((_, n_wins)) => *n_wins += 1,
+                    None => wins.push((name.clone(), 1)),
+                }
+            }
+        }
+        wins.sort_by_key(|(_, n_wins)| std::cmp::Reverse(*n_wins));
+        wins
+    }
+
+    pub fn print_standings(&self) {
+        let wins = self.wins();
+        if wins.is_empty() {
+            println!("No campaign games have been won yet.");
+            return;
+        }
+        let standings: Vec<String> = wins.iter().map(|(name, n_wins)| format!("{} {}", name, n_wins)).collect();
+        println!("Campaign wins: {}", standings.join(", "));
+    }
+
+    // Records a win for the winner and their teammates, and lets the winner
+    // change the map for the games to come.
+    pub fn record_game(&mut self, map: &mut Map, players: &[Player], winner_idx: usize, input: &mut Input) {
+        for player_idx in 0..players.len() {
+            if !players[player_idx].is_neutral && teams::are_allies(players, winner_idx, player_idx) {
+                self.entries.push(Entry::Win(players[player_idx].name.clone()));
+            }
+        }
+
+        println!("\n==== {} leaves their mark on the map ====", players[winner_idx].name);
+        loop {
+            let mark = input.prompt(
+                "legacy",
+                "Found a city, build a bunker or rename a territory (city <territory index>, bunker <territory index> or rename <territory index> <new name>): ");
+            match leave_mark(map, &mark) {
+                Ok(entry) => {
+                    self.entries.push(entry);
+                    return;
+                }
+                Err(error) => input.reject(&error),
+            }
+        }
+    }
+}
+
+// Makes the change the winner asked for to the map, e.g. "city 5" or
+// "rename 6 New Siberia", and returns the campaign entry recording it.
+fn leave_mark(map: &mut Map, mark: &str) -> Result<Entry, String> {
+    let mut words = mark.splitn(3, ' ');
+    let kind = words.next().unwrap_or("");
+    let territory_index: u32 = words
+        .next()
+        .and_then(|index| index.parse().ok())
+        .filter(|index| (*index as usize) < map.territories.node_count())
+        .ok_or("Expected a territory index.")?;
+    let territory = &mut map.territories[NodeIndex::new(territory_index as usize)];
+    let territory_name = territory.name.to_string();
+
+    match kind {
+        "city" if territory.city => Err(format!("{} already has a city.", territory_name)),
+        "city" => {
+            territory.city = true;
+            println!("A city is founded in {}.", territory_name);
+            Ok(Entry::City(territory_name))
+        }
+        "bunker" if territory.bunker => Err(format!("{} already has a bunker.", territory_name)),
+        "bunker" => {
+            territory.bunker = true;
+            println!("A bunker is built in {}.", territory_name);
+            Ok(Entry::Bunker(territory_name))
+        }
+        "rename" => {
+            let new_name = words.next().unwrap_or("").trim();
+            if new_name.is_empty() || new_name.contains(',') || new_name.contains(':') || new_name.contains('@') {
+                return Err(format!("\"{}\" cannot be used as a name.", new_name));
+            }
+            if map.find_territory(new_name).is_some() || map.find_continent(new_name).is_some() {
+                return Err(format!("The name {} is already taken.", new_name));
+            }
+            map.territories[NodeIndex::new(territory_index as usize)].name = new_name.to_string();
+            println!("{} is now called {}.", territory_name, new_name);
+            Ok(Entry::Rename(territory_name, new_name.to_string()))
+        }
+        _ => Err(format!("Unknown mark \"{}\", expected city, bunker or rename.", kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn campaign_changes_carry_over_to_the_next_game() {
+        let mut campaign = Campaign::parse("win Ann\ncity Western Australia\nwin Bo\nrename Siberia, Bo's Landing\nwin Bo\nbunker Bo's Landing\n").unwrap();
+        let mut map = Map::default_map();
+        campaign.apply(&mut map).unwrap();
+
+        assert!(map.territories[NodeIndex::new(0)].city);
+        assert_eq!(map.territories[NodeIndex::new(6)].name, "Bo's Landing");
+        assert!(map.territories[NodeIndex::new(6)].bunker);
+        assert_eq!(campaign.wins(), vec![("Bo".to_string(), 2), ("Ann".to_string(), 1)]);
+
+        // Ann and Cy win as a team, and Ann renames China, after asking for a
+        // city where there already is one.
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+        ];
+        players[0].team = Some(1);
+        players[2].team = Some(1);
+        let mut input = Input::from_lines(&["city 0", "rename 5 Ann's Harbour"]);
+        campaign.record_game(&mut map, &players, 0, &mut input);
+
+        assert_eq!(map.territories[NodeIndex::new(5)].name, "Ann's Harbour");
+        assert!(campaign.to_text().ends_with("win Ann\nwin Cy\nrename China, Ann's Harbour\n"));
+        let mut next_map = Map::default_map();
+        Campaign::parse(&campaign.to_text()).unwrap().apply(&mut next_map).unwrap();
+        assert_eq!(next_map.to_text(), map.to_text());
+    }
+}
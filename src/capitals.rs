@@ -0,0 +1,98 @@
+// Capital conquest: at the end of setup every player designates one of their
+// territories as their capital, and the game is won by holding enough capitals.
+
+use petgraph::graph::UnGraph;
+
+use crate::input::Input;
+use crate::rules::RuleSet;
+use crate::{teams, territory_owner, Border, Player, Territory};
+
+pub fn choose_capitals(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    input: &mut Input) {
+    for player in players.iter_mut().filter(|player| !player.is_neutral) {
+        let mut sorted_territory_indices: Vec<u32> = player.army_per_territory.keys().cloned().collect();
+        sorted_territory_indices.sort();
+
+        println!("\nPlayer: {}, current territories:", player.name);
+        for territory_index in sorted_territory_indices {
+            let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap().name;
+            let armies = player.army_per_territory.get(&territory_index).unwrap();
+            println!("Territory index: {}, territory name: {}, Armies: {}", territory_index, territory_name, armies);
+        }
+
+        loop {
+            let selected_index: u32 = input.prompt_number("capital", "Choose a territory index to be your capital: ");
+            if player.army_per_territory.contains_key(&selected_index) {
+                let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(selected_index as usize)).unwrap().name;
+                println!("Player {} has made {} their capital.", player.name, territory_name);
+                player.capital = Some(selected_index);
+                break;
+            }
+            input.reject("You do not own this territory, please select again.");
+        }
+    }
+}
+
+// Returns a marker to show next to the territory name if it is a capital. The
+// marker names the player who founded the capital, which is not necessarily the
+// player holding it now.
+pub fn capital_marker(players: &[Player], territory_index: u32) -> String {
+    match players.iter().find(|player| player.capital == Some(territory_index)) {
+        Some(founder) => format!(" [Capital of {}]", founder.name),
+        None => String::new(),
+    }
+}
+
+// Returns the index of a player who holds enough capitals to win, if any.
+// Capitals held by teammates count towards the whole team.
+pub fn check_capitals(players: &[Player], rules: &RuleSet) -> Option<usize> {
+    let n_acting_players = players.iter().filter(|player| !player.is_neutral).count();
+    let capitals_to_win = rules.capitals_to_win_for(n_acting_players);
+
+    let mut capitals_held = vec![0; players.len()];
+    for capital in players.iter().filter_map(|player| player.capital) {
+        if let Some(owner_idx) = territory_owner(players, capital) {
+            capitals_held[owner_idx] += 1;
+        }
+    }
+
+    (0..players.len()).find(|player_idx| {
+        let team_capitals_held: u32 = (0..players.len())
+            .filter(|other_idx| teams::are_allies(players, *player_idx, *other_idx))
+            .map(|other_idx| capitals_held[other_idx])
+            .sum();
+        !players[*player_idx].is_neutral && team_capitals_held >= capitals_to_win
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn capitals_are_won_by_holding_the_required_number() {
+        let rules = RuleSet::classic();
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+            Player::new("Di".to_string(), Color::Yellow),
+        ];
+        for (player_idx, player) in players.iter_mut().enumerate() {
+            player.army_per_territory.insert(player_idx as u32, 1);
+            player.capital = Some(player_idx as u32);
+        }
+        assert_eq!(check_capitals(&players, &rules), None);
+
+        // With four players, three capitals are enough.
+        players[1].army_per_territory.remove(&1);
+        players[0].army_per_territory.insert(1, 1);
+        assert_eq!(check_capitals(&players, &rules), None);
+        players[2].army_per_territory.remove(&2);
+        players[0].army_per_territory.insert(2, 1);
+        assert_eq!(check_capitals(&players, &rules), Some(0));
+    }
+}
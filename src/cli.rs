@@ -0,0 +1,246 @@
+// Command-line options. Anything given on the command line is not asked for
+// again during setup.
+
+use crate::map_generator::MapSettings;
+use crate::setup::{Placement, SetupMode};
+use crate::{Color, MAX_PLAYERS, PLAYER_COLORS};
+
+const HELP: &str = "\
+Hazard, the Risk-like strategy game
+
+Usage: hazard_game [COMMAND] [OPTIONS]
+
+Commands:
+  play                 Play a game, reading moves from the keyboard (default)
+  replay <script>      Play a game from a script of labelled commands
+  export-map <file>    Write the map to a Graphviz .dot file or an .svg image
+  check-map            Check the map for mistakes and show how balanced it is
+  generate-map <file>  Write a randomly generated map to a map file
+  edit-map <file>      Create or change a map file with the map editor
+  puzzle <file>        Attempt a puzzle, then see how the solver would play it.
+                       The solver slows down exponentially with the number of
+                       attacks and armies, so keep puzzles small
+
+Options:
+  --players <names>    Comma-separated player names, each optionally with a
+                       colour, e.g. \"Ann:Red,Bo,Cy:Cyan\"
+  --setup <mode>       How territories are shared out: random or draft
+  --placement <mode>   How starting armies are placed: manual or auto
+  --seed <number>      Seed for dice, dealing and generated maps, to make
+                       games and maps reproducible
+  --map <map>          Map to play on: \"default\" or a map file
+  --rules <rules>      Rules preset (classic or house) or rules file
+  --script <file>      Read every decision from a script, as with replay
+  --snapshot <file>    Write the board to a .dot or .svg file after every turn
+  --scenario <file>    Start from the position in a scenario file, skipping setup
+  --campaign <file>    Play the next game of a legacy campaign, whose winners
+                       change the map for the games that follow
+  --territories <n>    Number of territories of a generated map (24)
+  --continents <n>     Number of continents of a generated map (4)
+  --degree <number>    Average number of neighbours in a generated map (3)
+  -h, --help           Show this help
+";
+
+#[derive(Debug, Default, PartialEq)]
+pub enum Subcommand {
+    #[default]
+    Play,
+    Replay,
+    ExportMap,
+    CheckMap,
+    GenerateMap,
+    EditMap,
+    Puzzle,
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub subcommand: Subcommand,
+    // Player names, with the colours they chose if any.
+    pub players: Option<Vec<(String, Option<Color>)>>,
+    pub setup_mode: Option<SetupMode>,
+    pub placement: Option<Placement>,
+    pub seed: Option<u64>,
+    pub map: Option<String>,
+    pub rules: Option<String>,
+    pub script: Option<String>,
+    // Where export-map, generate-map and edit-map write the map.
+    pub export_file: Option<String>,
+    pub map_settings: MapSettings,
+    pub snapshot: Option<String>,
+    pub scenario: Option<String>,
+    pub campaign: Option<String>,
+    pub puzzle: Option<String>,
+    pub help: bool,
+}
+
+pub fn print_help() {
+    print!("{}", HELP);
+}
+
+// Parses the arguments after the program name.
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| {
+            args.next().ok_or_else(|| format!("{} requires a value", option))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--players" => options.players = Some(parse_players(value(arg)?)?),
+            "--setup" => {
+                options.setup_mode = Some(match value(arg)?.as_str() {
+                    "random" => SetupMode::Random,
+                    "draft" => SetupMode::Draft,
+                    other => return Err(format!("Unknown setup mode \"{}\", expected random or draft", other)),
+                })
+            }
+            "--placement" => {
+                options.placement = Some(match value(arg)?.as_str() {
+                    "manual" => Placement::Manual,
+                    "auto" => Placement::Automatic,
+                    other => return Err(format!("Unknown placement \"{}\", expected manual or auto", other)),
+                })
+            }
+            "--seed" => {
+                let seed = value(arg)?;
+                options.seed = Some(seed.parse().map_err(|_| format!("Invalid seed \"{}\"", seed))?);
+            }
+            "--map" => options.map = Some(value(arg)?.clone()),
+            "--territories" => options.map_settings.n_territories = parse_number(arg, value(arg)?)?,
+            "--continents" => options.map_settings.n_continents = parse_number(arg, value(arg)?)?,
+            "--degree" => options.map_settings.average_degree = parse_number(arg, value(arg)?)?,
+            "--rules" => options.rules = Some(value(arg)?.clone()),
+            "--script" => options.script = Some(value(arg)?.clone()),
+            "--snapshot" => options.snapshot = Some(value(arg)?.clone()),
+            "--scenario" => options.scenario = Some(value(arg)?.clone()),
+            "--campaign" => options.campaign = Some(value(arg)?.clone()),
+            other if other.starts_with('-') => return Err(format!("Unknown option \"{}\"", other)),
+            other => positional.push(other.to_string()),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    match positional.next().as_deref() {
+        None | Some("play") => {}
+        Some("replay") => {
+            options.subcommand = Subcommand::Replay;
+            let script = positional.next().ok_or("replay requires a script file")?;
+            if options.script.is_some() {
+                return Err("replay takes its script in place of --script".to_string());
+            }
+            options.script = Some(script);
+        }
+        Some("check-map") => options.subcommand = Subcommand::CheckMap,
+        Some("export-map") => {
+            options.subcommand = Subcommand::ExportMap;
+            options.export_file = Some(positional.next().ok_or("export-map requires a .dot or .svg file")?);
+        }
+        Some("generate-map") => {
+            options.subcommand = Subcommand::GenerateMap;
+            options.export_file = Some(positional.next().ok_or("generate-map requires a map file to write")?);
+        }
+        Some("edit-map") => {
+            options.subcommand = Subcommand::EditMap;
+            options.export_file = Some(positional.next().ok_or("edit-map requires a map file")?);
+        }
+        Some("puzzle") => {
+            options.subcommand = Subcommand::Puzzle;
+            options.puzzle = Some(positional.next().ok_or("puzzle requires a puzzle file")?);
+        }
+        Some(other) => return Err(format!("Unknown command \"{}\"", other)),
+    }
+    if let Some(extra) = positional.next() {
+        return Err(format!("Unexpected argument \"{}\"", extra));
+    }
+
+    Ok(options)
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid number \"{}\" for {}", value, option))
+}
+
+fn parse_players(value: &str) -> Result<Vec<(String, Option<Color>)>, String> {
+    let mut players: Vec<(String, Option<Color>)> = Vec::new();
+    for player in value.split(',') {
+        let (name, color) = match player.split_once(':') {
+            // "bot:heuristic" and the like would be computer players, which
+            // the game does not have.
+            Some((name, _)) if name.trim() == "bot" => {
+                return Err(format!("Computer players such as \"{}\" are not supported", player.trim()));
+            }
+            Some((name, color_name)) => {
+                let color = Color::from_name(color_name.trim())
+                    .filter(|color| PLAYER_COLORS.contains(color))
+                    .ok_or_else(|| format!("Unknown player colour \"{}\"", color_name.trim()))?;
+                if players.iter().any(|(_, other_color)| other_color.as_ref() == Some(&color)) {
+                    return Err(format!("{} is chosen by more than one player", color.name()));
+                }
+                (name.trim(), Some(color))
+            }
+            None => (player.trim(), None),
+        };
+        if name.is_empty() {
+            return Err("Player names cannot be empty".to_string());
+        }
+        players.push((name.to_string(), color));
+    }
+    if players.len() > MAX_PLAYERS {
+        return Err(format!("At most {} players can play", MAX_PLAYERS));
+    }
+    Ok(players)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn options_and_subcommands_are_parsed() {
+        let options = parse(&["--players", "Ann:pink, Bo", "--setup", "draft", "--placement", "auto", "--seed", "42"]).unwrap();
+        assert_eq!(options.subcommand, Subcommand::Play);
+        assert_eq!(options.players, Some(vec![("Ann".to_string(), Some(Color::Pink)), ("Bo".to_string(), None)]));
+        assert_eq!(options.setup_mode, Some(SetupMode::Draft));
+        assert_eq!(options.placement, Some(Placement::Automatic));
+        assert_eq!(options.seed, Some(42));
+
+        let options = parse(&["replay", "game.txt", "--rules", "house"]).unwrap();
+        assert_eq!(options.subcommand, Subcommand::Replay);
+        assert_eq!(options.script.as_deref(), Some("game.txt"));
+        assert_eq!(options.rules.as_deref(), Some("house"));
+
+        let options = parse(&["export-map", "board.svg"]).unwrap();
+        assert_eq!(options.subcommand, Subcommand::ExportMap);
+        assert_eq!(options.export_file.as_deref(), Some("board.svg"));
+
+        let options = parse(&["generate-map", "islands.txt", "--territories", "40", "--degree", "2.5"]).unwrap();
+        assert_eq!(options.subcommand, Subcommand::GenerateMap);
+        assert_eq!(options.map_settings, MapSettings { n_territories: 40, n_continents: 4, average_degree: 2.5 });
+    }
+
+    #[test]
+    fn invalid_arguments_are_reported() {
+        assert_eq!(parse(&["--setup", "deal"]).unwrap_err(), "Unknown setup mode \"deal\", expected random or draft");
+        assert_eq!(parse(&["--seed"]).unwrap_err(), "--seed requires a value");
+        assert_eq!(parse(&["--players", "Ann,,Bo"]).unwrap_err(), "Player names cannot be empty");
+        assert_eq!(parse(&["--players", "A,B,C,D,E,F,G,H,I"]).unwrap_err(), "At most 8 players can play");
+        assert_eq!(parse(&["--players", "Ann:Red,Bo:red"]).unwrap_err(), "Red is chosen by more than one player");
+        assert_eq!(parse(&["--players", "Ann:Grey"]).unwrap_err(), "Unknown player colour \"Grey\"");
+        assert_eq!(parse(&["--players", "Ann,bot:heuristic"]).unwrap_err(), "Computer players such as \"bot:heuristic\" are not supported");
+        assert_eq!(parse(&["replay"]).unwrap_err(), "replay requires a script file");
+        assert_eq!(parse(&["export-map"]).unwrap_err(), "export-map requires a .dot or .svg file");
+        assert_eq!(parse(&["puzzle"]).unwrap_err(), "puzzle requires a puzzle file");
+        assert_eq!(parse(&["--territories", "many"]).unwrap_err(), "Invalid number \"many\" for --territories");
+        assert_eq!(parse(&["fight"]).unwrap_err(), "Unknown command \"fight\"");
+        assert_eq!(parse(&["--colour"]).unwrap_err(), "Unknown option \"--colour\"");
+    }
+}
@@ -0,0 +1,505 @@
+// Combat resolution. Dice are injectable so that battles can be replayed with
+// fixed rolls, e.g. in tests.
+
+use petgraph::graph::UnGraph;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::economy::{hand_over_buildings, has_building, Building};
+use crate::input::Input;
+use crate::rules::RuleSet;
+use crate::undo::{Action, History};
+use crate::{Border, Player, Territory};
+
+pub trait Dice {
+    // Rolls a single six-sided die.
+    fn roll(&mut self) -> u8;
+}
+
+// Dice rolled from a random number generator, which is seeded with "--seed" to
+// make games reproducible.
+pub struct RandomDice(pub StdRng);
+
+impl Dice for RandomDice {
+    fn roll(&mut self) -> u8 {
+        self.0.gen_range(1..=6)
+    }
+}
+
+// Dice that repeat a fixed sequence of rolls, e.g. for puzzles.
+pub struct LoadedDice {
+    rolls: Vec<u8>,
+    next: usize,
+}
+
+impl LoadedDice {
+    pub fn new(rolls: &[u8]) -> Self {
+        LoadedDice { rolls: rolls.to_vec(), next: 0 }
+    }
+}
+
+impl Dice for LoadedDice {
+    fn roll(&mut self) -> u8 {
+        let roll = self.rolls[self.next % self.rolls.len()];
+        self.next += 1;
+        roll
+    }
+}
+
+// Sorts both sets of rolls in descending order and compares them pairwise, the
+// highest attacking die against the highest defending die and so on. Ties go to
+// the defender. Returns the number of armies lost by the attacker and defender.
+pub fn compare_dice(attacking_dice_rolls: &mut [u8], defending_dice_rolls: &mut [u8]) -> (u32, u32) {
+    attacking_dice_rolls.sort_by(|a, b| b.cmp(a)); // Sort descending
+    defending_dice_rolls.sort_by(|a, b| b.cmp(a)); // Sort descending
+
+    let mut attacker_losses = 0;
+    let mut defender_losses = 0;
+    for (i, (attacking_roll, defending_roll)) in attacking_dice_rolls.iter().zip(defending_dice_rolls.iter()).enumerate() {
+        if attacking_roll > defending_roll {
+            println!("Attacker wins comparison {}: {} vs {}", i + 1, attacking_roll, defending_roll);
+            defender_losses += 1;
+        } else {
+            println!("Defender wins comparison {}: {} vs {}", i + 1, defending_roll, attacking_roll);
+            attacker_losses += 1;
+        }
+    }
+    (attacker_losses, defender_losses)
+}
+
+// How well the defender holds a territory: whether it is fortified, and how much
+// the terrain, a city and a bunker make their highest die count for more, along
+// with what gives that bonus.
+pub fn defence_of(
+    territories: &UnGraph<Territory, Border>,
+    defender: &Player,
+    target_territory_index: u32,
+    rules: &RuleSet) -> (bool, u32, Vec<&'static str>) {
+    let target_territory = &territories[petgraph::graph::NodeIndex::new(target_territory_index as usize)];
+    let is_fortified = target_territory.fortress
+        || has_building(defender, target_territory_index, Building::Fortification);
+
+    let mut defence_bonus = rules.defence_bonus_on(target_territory.terrain);
+    let mut advantages = vec![target_territory.terrain.name()];
+    if target_territory.city || has_building(defender, target_territory_index, Building::City) {
+        defence_bonus += rules.city_defence_bonus;
+        advantages.push("city");
+    }
+    if target_territory.bunker {
+        defence_bonus += rules.bunker_defence_bonus;
+        advantages.push("bunker");
+    }
+    (is_fortified, defence_bonus, advantages)
+}
+
+pub fn raise_roll(roll: u8, defence_bonus: u32) -> u8 {
+    roll.saturating_add(defence_bonus.min(u8::MAX as u32) as u8)
+}
+
+// Resolves one round of an attack, moving armies into the target territory if it
+// is conquered. Returns true if the attack cannot continue, either because the
+// target was conquered or because the attacker has too few armies left.
+#[allow(clippy::too_many_arguments)]
+pub fn perform_attack(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    attacker_idx: usize,
+    defender_idx: usize,
+    attacking_territory_index: u32,
+    target_territory_index: u32,
+    rules: &RuleSet,
+    history: &mut History,
+    input: &mut Input,
+    dice: &mut dyn Dice) -> bool {
+
+    // We currently hard-code to using the maximum number of armies rather
+    // than asking every time.
+    let use_max_armies = true;
+
+    let attacking_territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(attacking_territory_index as usize)).unwrap().name;
+    let target_territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(target_territory_index as usize)).unwrap().name;
+
+    println!(
+        "Player {} is attacking from {} to {}",
+        players[attacker_idx].name, attacking_territory_name, target_territory_name);
+
+    let n_attack_armies = *players[attacker_idx].army_per_territory.get(&attacking_territory_index).unwrap();
+    println!("Player {} has {} armies in {}",
+        players[attacker_idx].name,
+        n_attack_armies,
+        attacking_territory_name);
+    let border = territories
+        .find_edge(
+            petgraph::graph::NodeIndex::new(attacking_territory_index as usize),
+            petgraph::graph::NodeIndex::new(target_territory_index as usize))
+        .map(|edge| territories[edge])
+        .unwrap();
+    let max_attack_dice = rules.max_attack_dice_across(border.kind);
+    if max_attack_dice < rules.max_attack_dice {
+        println!("Attacking across the sea with at most {} dice", max_attack_dice);
+    }
+    let max_attack_armies = std::cmp::min(n_attack_armies - rules.armies_left_behind, max_attack_dice);
+
+    let mut n_attacking_armies;
+    if use_max_armies {
+        n_attacking_armies = max_attack_armies;
+        println!("Player {} is attacking with {} armies", players[attacker_idx].name, n_attacking_armies);
+    }
+    else {
+        if max_attack_armies == 1 {
+            println!("Player {} must attack with 1 army from {}, hence proceeding with 1 army.",
+                players[attacker_idx].name,
+                attacking_territory_name);
+            n_attacking_armies = 1;
+        }
+        else {
+            n_attacking_armies = input.prompt_number(
+                "dice",
+                &format!("Choose number of armies to attack with (between 1 and {}): ", max_attack_armies));
+            if n_attacking_armies > max_attack_armies {
+                n_attacking_armies = max_attack_armies;
+                println!("Requested too many attacking armies, reducing to {}", n_attacking_armies);
+            }
+            if n_attacking_armies == 0 {
+                n_attacking_armies = 1;
+                println!("Cannot attack with zero armies, increasing to 1.");
+            }
+        }
+    }
+
+    let n_defend_armies = *players[defender_idx].army_per_territory.get(&target_territory_index).unwrap();
+    println!("Player {} has {} armies in {}",
+        players[defender_idx].name,
+        n_defend_armies,
+        target_territory_name);
+    let (is_fortified, defence_bonus, advantages) = defence_of(territories, &players[defender_idx], target_territory_index, rules);
+    if is_fortified {
+        println!("{} is fortified, defended with up to {} dice", target_territory_name, rules.fortress_defend_dice);
+    }
+    let max_defend_armies = std::cmp::min(n_defend_armies, rules.max_defend_dice_with(is_fortified));
+
+    let mut n_defending_armies;
+    if use_max_armies {
+        n_defending_armies = max_defend_armies;
+        println!("Player {} is defending with {} armies", players[defender_idx].name, n_defending_armies);
+    }
+    else {
+        n_defending_armies = input.prompt_number(
+            "dice",
+            &format!("Choose number of armies to defend with (between 1 and {}): ", max_defend_armies));
+        if n_defending_armies > max_defend_armies {
+            n_defending_armies = max_defend_armies;
+            println!("Requested too many defending armies, reducing to {}", n_defending_armies);
+        }
+        if n_defending_armies == 0 {
+            n_defending_armies = 1;
+            println!("Cannot defend with zero armies, increasing to 1.");
+        }
+    }
+
+    // Nothing before a dice roll can be taken back.
+    history.clear();
+
+    let mut attacking_dice_rolls = Vec::<u8>::new();
+    for _ in 0..n_attacking_armies {
+        let dice_roll = dice.roll();
+        println!("Attacker rolled: {}", dice_roll);
+        attacking_dice_rolls.push(dice_roll);
+    }
+    let mut defending_dice_rolls = Vec::<u8>::new();
+    for _ in 0..n_defending_armies {
+        let dice_roll = dice.roll();
+        println!("Defender rolled: {}", dice_roll);
+        defending_dice_rolls.push(dice_roll);
+    }
+
+    if defence_bonus > 0 {
+        defending_dice_rolls.sort_by(|a, b| b.cmp(a));
+        let raised_roll = raise_roll(defending_dice_rolls[0], defence_bonus);
+        println!("{} ({}) favours the defender: their highest die counts {} instead of {}",
+            target_territory_name,
+            advantages.join(", "),
+            raised_roll,
+            defending_dice_rolls[0]);
+        defending_dice_rolls[0] = raised_roll;
+    }
+
+    let (attacker_losses, defender_losses) = compare_dice(&mut attacking_dice_rolls, &mut defending_dice_rolls);
+    *players[attacker_idx].army_per_territory.get_mut(&attacking_territory_index).unwrap() -= attacker_losses;
+    *players[defender_idx].army_per_territory.get_mut(&target_territory_index).unwrap() -= defender_losses;
+
+    let new_n_attack_armies = *players[attacker_idx].army_per_territory.get(&attacking_territory_index).unwrap();
+    println!("Player {} now has {} armies in {}",
+        players[attacker_idx].name,
+        new_n_attack_armies,
+        attacking_territory_name);
+
+    let new_n_defend_armies = *players[defender_idx].army_per_territory.get(&target_territory_index).unwrap();
+    println!("Player {} now has {} armies in {}",
+        players[defender_idx].name,
+        new_n_defend_armies,
+        target_territory_name);
+
+    if new_n_defend_armies == 0 {
+        players[defender_idx].army_per_territory.remove(&target_territory_index);
+        hand_over_buildings(players, defender_idx, attacker_idx, target_territory_index);
+
+        println!("Player {} conquered territory {}!",
+            players[attacker_idx].name,
+            target_territory_name);
+
+        if players[defender_idx].army_per_territory.is_empty() && !players[defender_idx].is_neutral {
+            println!("Player {} has been eliminated!", players[defender_idx].name);
+        }
+
+        // We move at least the number of attacking armies used in the attack,
+        // up to the maximum number of armies minus those left behind in the
+        // attacking territory.
+        let max_movable_armies = new_n_attack_armies - rules.armies_left_behind;
+        let min_movable_armies = n_attacking_armies;
+        if min_movable_armies == max_movable_armies {
+            println!("Automatically moving {} armies into conquered territory {}",
+                min_movable_armies,
+                target_territory_name);
+            players[attacker_idx].army_per_territory.insert(target_territory_index, min_movable_armies);
+            let attacker_armies = players[attacker_idx].army_per_territory.get_mut(&attacking_territory_index).unwrap();
+            *attacker_armies -= min_movable_armies;
+            return true;
+        }
+
+        move_into_conquered_territory(
+            territories,
+            players,
+            attacker_idx,
+            attacking_territory_index,
+            target_territory_index,
+            min_movable_armies,
+            rules,
+            history,
+            input);
+    }
+
+    if new_n_attack_armies <= rules.armies_left_behind {
+        println!("Player {} only has {} armies left, attack on {} cannot continue",
+            players[attacker_idx].name,
+            new_n_attack_armies,
+            target_territory_name);
+    }
+
+    (new_n_defend_armies == 0) || (new_n_attack_armies <= rules.armies_left_behind)
+}
+
+
+// Asks the attacker how many armies to move into the territory they conquered,
+// and moves them. The move can be undone until the next dice roll.
+#[allow(clippy::too_many_arguments)]
+pub fn move_into_conquered_territory(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    attacker_idx: usize,
+    attacking_territory_index: u32,
+    target_territory_index: u32,
+    min_movable_armies: u32,
+    rules: &RuleSet,
+    history: &mut History,
+    input: &mut Input) {
+    let target_territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(target_territory_index as usize)).unwrap().name;
+    let max_movable_armies = players[attacker_idx].army_per_territory[&attacking_territory_index] - rules.armies_left_behind;
+    let before = players.to_vec();
+
+    let mut n_movable_armies = input.prompt_number(
+        "move",
+        &format!("Choose number of armies to move into conquered territory (between {} and {}): ",
+            min_movable_armies,
+            max_movable_armies));
+    if n_movable_armies > max_movable_armies {
+        n_movable_armies = max_movable_armies;
+        println!("Requested too many movable armies, reducing to {}", n_movable_armies);
+    }
+    if n_movable_armies < min_movable_armies {
+        n_movable_armies = min_movable_armies;
+        println!("Requested too few movable armies, increasing to {}", n_movable_armies);
+    }
+
+    players[attacker_idx].army_per_territory.insert(target_territory_index, n_movable_armies);
+    let attacker_armies = players[attacker_idx].army_per_territory.get_mut(&attacking_territory_index).unwrap();
+    *attacker_armies -= n_movable_armies;
+    println!("Player {} moved {} armies into {}.", players[attacker_idx].name, n_movable_armies, target_territory_name);
+
+    history.record(
+        Action::ConquestMove { from: attacking_territory_index, to: target_territory_index, min_armies: min_movable_armies },
+        before,
+        players);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_territories, Color};
+    use rand::SeedableRng;
+
+    // Every combination of values for the given number of dice.
+    fn all_rolls(n_dice: usize) -> Vec<Vec<u8>> {
+        let mut rolls = vec![Vec::new()];
+        for _ in 0..n_dice {
+            rolls = rolls
+                .iter()
+                .flat_map(|roll| (1..=6).map(move |value| [roll.clone(), vec![value]].concat()))
+                .collect();
+        }
+        rolls
+    }
+
+    #[test]
+    fn dice_are_compared_highest_first_and_ties_go_to_the_defender() {
+        assert_eq!(compare_dice(&mut [6], &mut [5]), (0, 1));
+        assert_eq!(compare_dice(&mut [5], &mut [5]), (1, 0));
+        assert_eq!(compare_dice(&mut [1, 6, 2], &mut [5, 1]), (0, 2));
+        assert_eq!(compare_dice(&mut [6, 3, 3], &mut [3, 6]), (2, 0));
+        assert_eq!(compare_dice(&mut [6, 2, 1], &mut [5, 4]), (1, 1));
+        assert_eq!(compare_dice(&mut [4], &mut [3, 6]), (1, 0));
+    }
+
+    #[test]
+    fn every_dice_combination_loses_one_army_per_comparison() {
+        for n_attacking_dice in 1..=3 {
+            for n_defending_dice in 1..=2 {
+                for attacking_rolls in all_rolls(n_attacking_dice) {
+                    for defending_rolls in all_rolls(n_defending_dice) {
+                        let (attacker_losses, defender_losses) =
+                            compare_dice(&mut attacking_rolls.clone(), &mut defending_rolls.clone());
+                        assert_eq!(
+                            (attacker_losses + defender_losses) as usize,
+                            std::cmp::min(n_attacking_dice, n_defending_dice));
+
+                        // The highest dice are compared first, so the attacker loses at
+                        // least one army unless they rolled above every defending die.
+                        if attacking_rolls.iter().max() <= defending_rolls.iter().max() {
+                            assert!(attacker_losses >= 1);
+                        }
+
+                        // Raising any attacking die never makes the attacker lose more.
+                        for i in 0..n_attacking_dice {
+                            let mut raised_rolls = attacking_rolls.clone();
+                            raised_rolls[i] = 6;
+                            let (raised_attacker_losses, _) =
+                                compare_dice(&mut raised_rolls, &mut defending_rolls.clone());
+                            assert!(raised_attacker_losses <= attacker_losses);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn attacks_conserve_armies_and_never_empty_the_source_territory() {
+        let territories = setup_territories();
+        let rules = RuleSet::classic();
+        let mut rng = StdRng::seed_from_u64(36);
+
+        // Western Australia (0) attacks Indonesia (3) until the attack ends.
+        for _ in 0..500 {
+            let mut players = vec![
+                Player::new("Ann".to_string(), Color::Red),
+                Player::new("Bo".to_string(), Color::Blue),
+            ];
+            players[0].army_per_territory.insert(0, rng.gen_range(2..=20));
+            players[1].army_per_territory.insert(3, rng.gen_range(1..=20));
+            let mut dice = RandomDice(StdRng::seed_from_u64(rng.gen()));
+
+            loop {
+                let attacking_armies = players[0].army_per_territory[&0];
+                let defending_armies = players[1].army_per_territory[&3];
+                let n_attacking_dice = std::cmp::min(attacking_armies - rules.armies_left_behind, rules.max_attack_dice);
+                let n_comparisons = std::cmp::min(n_attacking_dice, std::cmp::min(defending_armies, rules.max_defend_dice));
+
+                let mut input = Input::from_lines(&["0"]);
+                let attack_finished = perform_attack(&territories, &mut players, 0, 1, 0, 3, &rules, &mut History::new(), &mut input, &mut dice);
+
+                let armies_after = players[0].total_armies() + players[1].total_armies();
+                assert_eq!(armies_after, attacking_armies + defending_armies - n_comparisons);
+                assert!(players[0].army_per_territory[&0] >= rules.armies_left_behind);
+
+                match players[1].army_per_territory.get(&3) {
+                    Some(armies) => assert!(*armies >= 1),
+                    None => {
+                        // The conquering armies move in, at least as many as attacked.
+                        assert!(attack_finished);
+                        assert!(players[0].army_per_territory[&3] >= n_attacking_dice);
+                        assert!(players[1].army_per_territory.is_empty());
+                    }
+                }
+                if attack_finished {
+                    assert!(!players[1].army_per_territory.contains_key(&3)
+                        || players[0].army_per_territory[&0] == rules.armies_left_behind);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn conquering_armies_move_in_within_the_allowed_range() {
+        let territories = setup_territories();
+        let rules = RuleSet::classic();
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+        ];
+        players[0].army_per_territory.insert(0, 10);
+        players[1].army_per_territory.insert(3, 1);
+
+        // Asking to move more armies than allowed leaves one army behind.
+        let mut input = Input::from_lines(&["50"]);
+        let mut dice = LoadedDice::new(&[6, 6, 6, 1]);
+        assert!(perform_attack(&territories, &mut players, 0, 1, 0, 3, &rules, &mut History::new(), &mut input, &mut dice));
+        assert_eq!(players[0].army_per_territory[&0], 1);
+        assert_eq!(players[0].army_per_territory[&3], 9);
+        assert!(players[1].army_per_territory.is_empty());
+    }
+
+    #[test]
+    fn sea_attacks_roll_fewer_dice() {
+        let territories = setup_territories();
+        let mut rules = RuleSet::classic();
+        rules.sea_attack_dice_penalty = 1;
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+        ];
+        players[0].army_per_territory.insert(0, 10);
+        players[1].army_per_territory.insert(3, 2);
+
+        // Western Australia (0) attacks Indonesia (3) across the sea with two
+        // dice, which beat both defending dice. With a third die the defender
+        // would have rolled the 6.
+        let mut input = Input::from_lines(&["2"]);
+        let mut dice = LoadedDice::new(&[6, 6, 1, 1, 6]);
+        assert!(perform_attack(&territories, &mut players, 0, 1, 0, 3, &rules, &mut History::new(), &mut input, &mut dice));
+        assert_eq!(players[0].army_per_territory[&0] + players[0].army_per_territory[&3], 10);
+    }
+
+    #[test]
+    fn fortresses_and_mountains_favour_the_defender() {
+        let mut territories = setup_territories();
+        let indonesia = &mut territories[petgraph::graph::NodeIndex::new(3)];
+        indonesia.terrain = crate::Terrain::Mountains;
+        indonesia.fortress = true;
+        let rules = RuleSet::classic();
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+        ];
+        players[0].army_per_territory.insert(0, 10);
+        players[1].army_per_territory.insert(3, 3);
+
+        // The fortress defends with three dice, and the highest one counts as a
+        // 5 in the mountains, which beats the attacker's 5.
+        let mut input = Input::from_lines(&["0"]);
+        let mut dice = LoadedDice::new(&[5, 5, 5, 4, 1, 1]);
+        assert!(!perform_attack(&territories, &mut players, 0, 1, 0, 3, &rules, &mut History::new(), &mut input, &mut dice));
+        assert_eq!(players[0].army_per_territory[&0], 9);
+        assert_eq!(players[1].army_per_territory[&3], 1);
+    }
+}
@@ -0,0 +1,319 @@
+// Diplomacy between players: non-aggression pacts lasting a number of rounds,
+// and territory swaps. Proposals are made on the proposer's turn and answered on
+// the recipient's turn, and only take effect once the recipient accepts.
+
+use petgraph::graph::UnGraph;
+
+use crate::economy::hand_over_buildings;
+use crate::input::Input;
+use crate::{teams, Border, Player, Territory};
+
+pub enum Proposal {
+    Pact { from: usize, to: usize, rounds: u32 },
+    // The proposer gives one of their territories in exchange for one of the
+    // recipient's. Armies stay on the territories.
+    TerritorySwap { from: usize, to: usize, give: u32, take: u32 },
+}
+
+impl Proposal {
+    fn proposer(&self) -> usize {
+        match self {
+            Proposal::Pact { from, .. } | Proposal::TerritorySwap { from, .. } => *from,
+        }
+    }
+
+    fn recipient(&self) -> usize {
+        match self {
+            Proposal::Pact { to, .. } | Proposal::TerritorySwap { to, .. } => *to,
+        }
+    }
+}
+
+pub struct Pact {
+    players: (usize, usize),
+    // The pact is no longer in force from the start of this round.
+    ends_at_round: u32,
+}
+
+#[derive(Default)]
+pub struct Diplomacy {
+    pacts: Vec<Pact>,
+    proposals: Vec<Proposal>,
+}
+
+impl Diplomacy {
+    pub fn new() -> Self {
+        Diplomacy::default()
+    }
+
+    pub fn has_pact(&self, player_idx: usize, other_idx: usize, round: u32) -> bool {
+        self.pacts.iter().any(|pact| {
+            round < pact.ends_at_round
+                && (pact.players == (player_idx, other_idx) || pact.players == (other_idx, player_idx))
+        })
+    }
+
+    pub fn remove_expired_pacts(&mut self, players: &[Player], round: u32) {
+        self.pacts.retain(|pact| {
+            let in_force = round < pact.ends_at_round;
+            if !in_force {
+                println!("The pact between {} and {} has expired.",
+                    players[pact.players.0].name,
+                    players[pact.players.1].name);
+            }
+            in_force
+        });
+    }
+
+    // Proposals to or from eliminated players can never be answered or honoured.
+    pub fn remove_proposals_of_eliminated(&mut self, players: &[Player]) {
+        let is_eliminated = |player_idx: usize| players[player_idx].army_per_territory.is_empty();
+        self.proposals.retain(|proposal| {
+            let in_play = !is_eliminated(proposal.proposer()) && !is_eliminated(proposal.recipient());
+            if !in_play {
+                println!("A proposal between {} and {} lapses, since one of them has been eliminated.",
+                    players[proposal.proposer()].name,
+                    players[proposal.recipient()].name);
+            }
+            in_play
+        });
+    }
+
+    pub fn print_status(&self, territories: &UnGraph<Territory, Border>, players: &[Player]) {
+        if self.pacts.is_empty() && self.proposals.is_empty() {
+            return;
+        }
+
+        println!("==== Diplomacy ====");
+        for pact in self.pacts.iter() {
+            println!("Active pact: {} and {}, until the start of round {}",
+                players[pact.players.0].name,
+                players[pact.players.1].name,
+                pact.ends_at_round);
+        }
+        for proposal in self.proposals.iter() {
+            println!("Pending proposal: {}", describe_proposal(proposal, territories, players));
+        }
+        println!();
+    }
+
+    // Lets the player answer every proposal addressed to them, then make new ones.
+    pub fn negotiate(
+        &mut self,
+        territories: &UnGraph<Territory, Border>,
+        players: &mut [Player],
+        player_idx: usize,
+        round: u32,
+        input: &mut Input) {
+        let (received, others): (Vec<Proposal>, Vec<Proposal>) = self.proposals
+            .drain(..)
+            .partition(|proposal| proposal.recipient() == player_idx);
+        self.proposals = others;
+
+        for proposal in received {
+            println!("Proposal: {}", describe_proposal(&proposal, territories, players));
+            let answer = input.prompt_yes_no("accept", "Do you accept? (y/n): ");
+            if matches!(answer.as_str(), "y" | "Y") {
+                self.accept(proposal, territories, players, round);
+            } else {
+                println!("Proposal rejected.");
+            }
+        }
+
+        loop {
+            let propose = input.prompt_yes_no("propose", "Do you want to propose a deal to another player? (y/n): ");
+            if !matches!(propose.as_str(), "y" | "Y") {
+                break;
+            }
+            if let Some(proposal) = prompt_proposal(territories, players, player_idx, input) {
+                println!("Proposal made: {}", describe_proposal(&proposal, territories, players));
+                self.proposals.push(proposal);
+            }
+        }
+    }
+
+    fn accept(
+        &mut self,
+        proposal: Proposal,
+        territories: &UnGraph<Territory, Border>,
+        players: &mut [Player],
+        round: u32) {
+        match proposal {
+            Proposal::Pact { from, to, rounds } => {
+                println!("{} and {} agree not to attack each other for {} rounds.",
+                    players[from].name,
+                    players[to].name,
+                    rounds);
+                self.pacts.push(Pact { players: (from, to), ends_at_round: round + rounds });
+            }
+            Proposal::TerritorySwap { from, to, give, take } => {
+                // Territories may have changed hands since the proposal was made.
+                if !players[from].army_per_territory.contains_key(&give)
+                    || !players[to].army_per_territory.contains_key(&take) {
+                    println!("The territories in this deal have changed hands, so it can no longer go ahead.");
+                    return;
+                }
+                let give_armies = players[from].army_per_territory.remove(&give).unwrap();
+                let take_armies = players[to].army_per_territory.remove(&take).unwrap();
+                players[to].army_per_territory.insert(give, give_armies);
+                players[from].army_per_territory.insert(take, take_armies);
+                hand_over_buildings(players, from, to, give);
+                hand_over_buildings(players, to, from, take);
+                println!("{} and {} have swapped {} and {}.",
+                    players[from].name,
+                    players[to].name,
+                    territory_name(territories, give),
+                    territory_name(territories, take));
+            }
+        }
+    }
+}
+
+fn territory_name(territories: &UnGraph<Territory, Border>, territory_index: u32) -> &str {
+    &territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap().name
+}
+
+fn describe_proposal(proposal: &Proposal, territories: &UnGraph<Territory, Border>, players: &[Player]) -> String {
+    match proposal {
+        Proposal::Pact { from, to, rounds } => format!(
+            "{} offers {} a non-aggression pact for {} rounds",
+            players[*from].name,
+            players[*to].name,
+            rounds),
+        Proposal::TerritorySwap { from, to, give, take } => format!(
+            "{} offers {} to {} in exchange for {}",
+            players[*from].name,
+            territory_name(territories, *give),
+            players[*to].name,
+            territory_name(territories, *take)),
+    }
+}
+
+fn prompt_proposal(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    player_idx: usize,
+    input: &mut Input) -> Option<Proposal> {
+    println!("Select player index to negotiate with:");
+    let candidates: Vec<usize> = (0..players.len())
+        .filter(|other_idx| {
+            !players[*other_idx].is_neutral
+                && !players[*other_idx].army_per_territory.is_empty()
+                && !teams::are_allies(players, player_idx, *other_idx)
+        })
+        .collect();
+    if candidates.is_empty() {
+        println!("There is nobody to negotiate with.");
+        return None;
+    }
+    for other_idx in candidates.iter() {
+        println!("Player index: {}, player name: {}", other_idx, players[*other_idx].name);
+    }
+
+    let other_idx: usize = input.prompt_number("propose", "Negotiating with player index: ");
+    if !candidates.contains(&other_idx) {
+        input.reject("You cannot negotiate with this player.");
+        return None;
+    }
+
+    let deal_type: u32 = input.prompt_number("propose", "Type 1 for a non-aggression pact, or 2 for a territory swap: ");
+    match deal_type {
+        1 => {
+            let rounds: u32 = input.prompt_number("propose", "Number of rounds the pact should last: ");
+            if rounds == 0 {
+                input.reject("A pact must last at least one round.");
+                return None;
+            }
+            Some(Proposal::Pact { from: player_idx, to: other_idx, rounds })
+        }
+        2 => {
+            for (label, idx) in [("Your", player_idx), ("Their", other_idx)] {
+                let mut sorted_territory_indices: Vec<u32> = players[idx].army_per_territory.keys().cloned().collect();
+                sorted_territory_indices.sort();
+                println!("{} territories:", label);
+                for territory_index in sorted_territory_indices {
+                    println!("Territory index: {}, territory name: {}", territory_index, territory_name(territories, territory_index));
+                }
+            }
+
+            let give: u32 = input.prompt_number("propose", "Territory index you offer: ");
+            if !players[player_idx].army_per_territory.contains_key(&give) {
+                input.reject("You do not own this territory.");
+                return None;
+            }
+            let take: u32 = input.prompt_number("propose", &format!("Territory index you want from {}: ", players[other_idx].name));
+            if !players[other_idx].army_per_territory.contains_key(&take) {
+                input.reject(&format!("{} does not own this territory.", players[other_idx].name));
+                return None;
+            }
+            Some(Proposal::TerritorySwap { from: player_idx, to: other_idx, give, take })
+        }
+        _ => {
+            input.reject("Invalid input, no proposal made.");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_territories, Color};
+
+    #[test]
+    fn pacts_block_attacks_until_they_expire() {
+        let territories = setup_territories();
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+        ];
+        let mut diplomacy = Diplomacy::new();
+        diplomacy.accept(Proposal::Pact { from: 0, to: 1, rounds: 2 }, &territories, &mut players, 3);
+
+        assert!(diplomacy.has_pact(0, 1, 3));
+        assert!(diplomacy.has_pact(1, 0, 4));
+        assert!(!diplomacy.has_pact(1, 0, 5));
+        diplomacy.remove_expired_pacts(&players, 5);
+        assert!(diplomacy.pacts.is_empty());
+    }
+
+    #[test]
+    fn territory_swaps_only_go_ahead_if_both_still_own_their_territories() {
+        let territories = setup_territories();
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+        ];
+        players[0].army_per_territory.insert(0, 3);
+        players[1].army_per_territory.insert(1, 5);
+
+        let mut diplomacy = Diplomacy::new();
+        diplomacy.accept(Proposal::TerritorySwap { from: 0, to: 1, give: 0, take: 1 }, &territories, &mut players, 1);
+        assert_eq!(players[0].army_per_territory.get(&1), Some(&5));
+        assert_eq!(players[1].army_per_territory.get(&0), Some(&3));
+
+        // Ann no longer owns territory 0, so the same deal cannot go ahead again.
+        diplomacy.accept(Proposal::TerritorySwap { from: 0, to: 1, give: 0, take: 1 }, &territories, &mut players, 1);
+        assert_eq!(players[0].army_per_territory.get(&1), Some(&5));
+    }
+
+    #[test]
+    fn proposals_involving_eliminated_players_lapse() {
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+        ];
+        players[0].army_per_territory.insert(0, 3);
+        players[2].army_per_territory.insert(2, 3);
+
+        let mut diplomacy = Diplomacy::new();
+        diplomacy.proposals.push(Proposal::Pact { from: 0, to: 1, rounds: 2 });
+        diplomacy.proposals.push(Proposal::Pact { from: 1, to: 2, rounds: 2 });
+        diplomacy.proposals.push(Proposal::Pact { from: 2, to: 0, rounds: 2 });
+        diplomacy.remove_proposals_of_eliminated(&players);
+
+        assert_eq!(diplomacy.proposals.len(), 1);
+        assert_eq!(diplomacy.proposals[0].recipient(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+// Gold and buildings, played with "economy = true". At the start of their turn
+// players earn gold for the territories and continents they hold, and spend it
+// on extra armies, cities or fortifications before placing their
+// reinforcements. Buildings stand on a territory and are taken over by whoever
+// conquers it.
+
+use petgraph::graph::UnGraph;
+
+use crate::input::Input;
+use crate::rules::RuleSet;
+use crate::scoring::held_continent_bonus;
+use crate::{Border, Continent, Player, Territory};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Building {
+    // Adds reinforcements every turn and makes the defender's highest die count more.
+    City,
+    // Lets the defender roll as many dice as in a fortress.
+    Fortification,
+}
+
+impl Building {
+    fn from_name(name: &str) -> Option<Building> {
+        [Building::City, Building::Fortification]
+            .into_iter()
+            .find(|building| building.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Building::City => "city",
+            Building::Fortification => "fortification",
+        }
+    }
+
+    fn cost(&self, rules: &RuleSet) -> u32 {
+        match self {
+            Building::City => rules.city_cost,
+            Building::Fortification => rules.fortification_cost,
+        }
+    }
+}
+
+pub fn has_building(player: &Player, territory_index: u32, building: Building) -> bool {
+    player.buildings.get(&territory_index).is_some_and(|buildings| buildings.contains(&building))
+}
+
+pub fn count_cities(player: &Player) -> u32 {
+    player.buildings
+        .values()
+        .flatten()
+        .filter(|building| **building == Building::City)
+        .count() as u32
+}
+
+// Hands the buildings on a territory over to its new owner, e.g. after a conquest.
+pub fn hand_over_buildings(players: &mut [Player], from_idx: usize, to_idx: usize, territory_index: u32) {
+    if let Some(buildings) = players[from_idx].buildings.remove(&territory_index) {
+        players[to_idx].buildings.insert(territory_index, buildings);
+    }
+}
+
+// Gold earned per turn for the territories held, and for the bonus of each
+// continent held in full.
+pub fn income(player: &Player, continents: &[Continent], rules: &RuleSet) -> u32 {
+    player.army_per_territory.len() as u32 * rules.gold_per_territory
+        + held_continent_bonus(player, continents) * rules.gold_per_continent_bonus
+}
+
+pub fn earn_gold(players: &mut [Player], player_idx: usize, continents: &[Continent], rules: &RuleSet) {
+    let earned_gold = income(&players[player_idx], continents, rules);
+    let player = &mut players[player_idx];
+    player.gold += earned_gold;
+    println!("Player {} earns {} gold and now has {} gold.", player.name, earned_gold, player.gold);
+}
+
+// Lets the player spend their gold, and returns the number of armies bought,
+// which are placed along with their reinforcements.
+pub fn purchase(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    player_idx: usize,
+    rules: &RuleSet,
+    input: &mut Input) -> u32 {
+    let mut bought_armies = 0;
+    loop {
+        println!("You have {} gold. An army costs {}, a city {} and a fortification {}.",
+            players[player_idx].gold,
+            rules.army_cost,
+            rules.city_cost,
+            rules.fortification_cost);
+        let buy_any = input.prompt_yes_no("buy", "Do you want to buy something? (y/n): ");
+        if !matches!(buy_any.as_str(), "y" | "Y") {
+            return bought_armies;
+        }
+
+        let order = input.prompt("buy", "Buy what? (army [count], city <territory index> or fortification <territory index>): ");
+        match buy(territories, &mut players[player_idx], &order, rules) {
+            Ok(n_armies) => bought_armies += n_armies,
+            Err(error) => input.reject(&error),
+        }
+    }
+}
+
+// Carries out a single order, such as "army 2" or "city 5", and returns the
+// number of armies bought.
+fn buy(territories: &UnGraph<Territory, Border>, player: &mut Player, order: &str, rules: &RuleSet) -> Result<u32, String> {
+    let (item, argument) = order.split_once(' ').unwrap_or((order, ""));
+    let argument = argument.trim();
+
+    if item == "army" || item == "armies" {
+        let n_armies: u32 = if argument.is_empty() {
+            1
+        } else {
+            argument.parse().map_err(|_| format!("\"{}\" is not a number.", argument))?
+        };
+        let cost = n_armies.saturating_mul(rules.army_cost);
+        if cost > player.gold {
+            return Err(format!("{} armies cost {} gold, but you only have {}.", n_armies, cost, player.gold));
+        }
+        player.gold -= cost;
+        println!("Player {} bought {} armies for {} gold.", player.name, n_armies, cost);
+        return Ok(n_armies);
+    }
+
+    let building = Building::from_name(item)
+        .ok_or_else(|| format!("Unknown purchase \"{}\", expected army, city or fortification.", item))?;
+    let territory_index: u32 = argument.parse().map_err(|_| format!("Expected: {} <territory index>", item))?;
+    if !player.army_per_territory.contains_key(&territory_index) {
+        return Err("You can only build on a territory you own.".to_string());
+    }
+    let territory = &territories[petgraph::graph::NodeIndex::new(territory_index as usize)];
+    let is_built = match building {
+        Building::City => territory.city,
+        Building::Fortification => territory.fortress,
+    };
+    if is_built || has_building(player, territory_index, building) {
+        return Err(format!("{} already has a {}.", territory.name, building.name()));
+    }
+    let cost = building.cost(rules);
+    if cost > player.gold {
+        return Err(format!("A {} costs {} gold, but you only have {}.", building.name(), cost, player.gold));
+    }
+    player.gold -= cost;
+    player.buildings.entry(territory_index).or_default().push(building);
+    println!("Player {} built a {} in {} for {} gold.", player.name, building.name(), territory.name, cost);
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_continents, setup_territories, Color};
+
+    #[test]
+    fn gold_is_earned_and_spent_on_armies_and_buildings() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let rules = RuleSet { economy: true, ..RuleSet::classic() };
+        let mut players = vec![Player::new("Ann".to_string(), Color::Red)];
+        for territory_index in [0, 1, 2, 3, 4] {
+            players[0].army_per_territory.insert(territory_index, 1);
+        }
+
+        // Five territories, and Australia with its bonus of 2.
+        earn_gold(&mut players, 0, &continents, &rules);
+        assert_eq!(players[0].gold, 7);
+        players[0].gold = 30;
+
+        // Building on a territory owned by someone else, or twice the same
+        // building, is refused.
+        let mut input = Input::from_lines(&[
+            "y", "army 2",
+            "y", "city 9",
+            "y", "city 3",
+            "y", "fortification 3",
+            "y", "city 3",
+            "n",
+        ]);
+        assert_eq!(purchase(&territories, &mut players, 0, &rules, &mut input), 2);
+        assert_eq!(players[0].gold, 30 - 6 - 12 - 8);
+        assert!(has_building(&players[0], 3, Building::City));
+        assert!(has_building(&players[0], 3, Building::Fortification));
+        assert_eq!(count_cities(&players[0]), 1);
+    }
+}
@@ -0,0 +1,302 @@
+// Exports the board as a Graphviz DOT file or as an SVG image. Territories are
+// grouped by continent and, when players are given, filled with the colour of
+// their owner and labelled with their army count. Territories are drawn where
+// the map places them, or around a circle per continent if it does not.
+
+use std::fs;
+
+use petgraph::graph::UnGraph;
+use petgraph::graph::EdgeReference;
+use petgraph::visit::EdgeRef;
+
+use crate::map::Map;
+use crate::{territory_owner, Border, BorderKind, Continent, Player, Territory};
+
+const NODE_RADIUS: f64 = 22.0;
+
+// Writes the board to a .dot or .svg file, depending on the file extension.
+pub fn write_board(path: &str, map: &Map, players: &[Player], show_armies: bool) -> Result<(), String> {
+    let contents = if path.ends_with(".svg") {
+        to_svg(map, players, show_armies)
+    } else if path.ends_with(".dot") || path.ends_with(".gv") {
+        to_dot(map, players, show_armies)
+    } else {
+        return Err(format!("{}: expected a .dot or .svg file", path));
+    };
+    fs::write(path, contents).map_err(|error| format!("{}: {}", path, error))
+}
+
+// Groups territory indices by continent. Territories that belong to no continent
+// end up in a last, unnamed group.
+fn territory_groups<'a>(territories: &UnGraph<Territory, Border>, continents: &'a [Continent]) -> Vec<(Option<&'a str>, Vec<u32>)> {
+    let mut groups: Vec<(Option<&str>, Vec<u32>)> = continents
+        .iter()
+        .map(|continent| (Some(continent.name.as_str()), continent.territories.clone()))
+        .collect();
+    let other_territory_indices: Vec<u32> = territories
+        .node_indices()
+        .map(|node_index| node_index.index() as u32)
+        .filter(|territory_index| !continents.iter().any(|continent| continent.territories.contains(territory_index)))
+        .collect();
+    if !other_territory_indices.is_empty() {
+        groups.push((None, other_territory_indices));
+    }
+    groups
+}
+
+fn fill_color(players: &[Player], territory_index: u32) -> String {
+    match territory_owner(players, territory_index) {
+        Some(owner_idx) => {
+            let (red, green, blue) = players[owner_idx].color.rgb();
+            format!("#{:02x}{:02x}{:02x}", red, green, blue)
+        }
+        None => "#ffffff".to_string(),
+    }
+}
+
+fn army_count(players: &[Player], territory_index: u32, show_armies: bool) -> Option<u32> {
+    if !show_armies {
+        return None;
+    }
+    territory_owner(players, territory_index).map(|owner_idx| players[owner_idx].army_per_territory[&territory_index])
+}
+
+fn territory_name(territories: &UnGraph<Territory, Border>, territory_index: u32) -> &str {
+    &territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap().name
+}
+
+pub fn to_dot(map: &Map, players: &[Player], show_armies: bool) -> String {
+    let territories = &map.territories;
+    let continents = &map.continents;
+    let mut dot = String::from("graph hazard {\n    node [shape=ellipse, style=filled];\n");
+
+    for (group_index, (continent_name, territory_indices)) in territory_groups(territories, continents).iter().enumerate() {
+        let indent = if continent_name.is_some() { "        " } else { "    " };
+        if let Some(continent_name) = continent_name {
+            dot.push_str(&format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", group_index, escape_dot(continent_name)));
+        }
+        for territory_index in territory_indices {
+            let mut label = escape_dot(territory_name(territories, *territory_index));
+            if let Some(armies) = army_count(players, *territory_index, show_armies) {
+                label.push_str(&format!("\\n{}", armies));
+            }
+            // Graphviz keeps territories at their position with "neato -n".
+            let position = match map.positions[*territory_index as usize] {
+                Some((x, y)) => format!(", pos=\"{},{}\"", x, -y),
+                None => String::new(),
+            };
+            dot.push_str(&format!("{}t{} [label=\"{}\", fillcolor=\"{}\"{}];\n",
+                indent,
+                territory_index,
+                label,
+                fill_color(players, *territory_index),
+                position));
+        }
+        if continent_name.is_some() {
+            dot.push_str("    }\n");
+        }
+    }
+
+    for edge in territories.edge_references() {
+        let (from, to) = border_ends(edge);
+        let mut attributes = Vec::new();
+        if edge.weight().kind == BorderKind::Sea {
+            attributes.push("style=dashed");
+        }
+        if edge.weight().one_way_from.is_some() {
+            attributes.push("dir=forward");
+        }
+        if attributes.is_empty() {
+            dot.push_str(&format!("    t{} -- t{};\n", from, to));
+        } else {
+            dot.push_str(&format!("    t{} -- t{} [{}];\n", from, to, attributes.join(", ")));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+// The ends of a border, with one-way routes going from the first to the second.
+fn border_ends(edge: EdgeReference<Border>) -> (usize, usize) {
+    match edge.weight().one_way_from {
+        Some(from) if from as usize == edge.target().index() => (edge.target().index(), edge.source().index()),
+        _ => (edge.source().index(), edge.target().index()),
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Places the territories where the map has them, scaled to fit the image and
+// with room around them for their names. Returns the position of every
+// territory and the size of the image, or nothing if some territory has no
+// position.
+#[allow(clippy::type_complexity)]
+fn map_layout(map: &Map) -> Option<(Vec<(f64, f64)>, (f64, f64))> {
+    let positions: Vec<(f64, f64)> = map.positions.iter().copied().collect::<Option<Vec<(f64, f64)>>>()?;
+    let min_x = positions.iter().map(|(x, _)| *x).fold(f64::MAX, f64::min);
+    let max_x = positions.iter().map(|(x, _)| *x).fold(f64::MIN, f64::max);
+    let min_y = positions.iter().map(|(_, y)| *y).fold(f64::MAX, f64::min);
+    let max_y = positions.iter().map(|(_, y)| *y).fold(f64::MIN, f64::max);
+    // Territories are at least three territory widths apart in the image.
+    let min_distance = positions
+        .iter()
+        .enumerate()
+        .flat_map(|(i, first)| positions[i + 1..].iter().map(move |second| ((first.0 - second.0).powi(2) + (first.1 - second.1).powi(2)).sqrt()))
+        .filter(|distance| *distance > 0.0)
+        .fold(f64::MAX, f64::min);
+    let scale = if min_distance == f64::MAX { 1.0 } else { (6.0 * NODE_RADIUS / min_distance).clamp(1.0, 4.0) };
+    let margin = 4.0 * NODE_RADIUS;
+    let scaled_positions = positions
+        .iter()
+        .map(|(x, y)| (margin + (x - min_x) * scale, margin + (y - min_y) * scale))
+        .collect();
+    Some((scaled_positions, ((max_x - min_x) * scale + 2.0 * margin, (max_y - min_y) * scale + 2.0 * margin)))
+}
+
+// Lays the continents out on a grid, with the territories of each continent on
+// a circle around its centre. Returns the position of every territory, the
+// centre and radius of every continent, and the size of the image.
+#[allow(clippy::type_complexity)]
+fn circle_layout(groups: &[(Option<&str>, Vec<u32>)], n_territories: usize) -> (Vec<(f64, f64)>, Vec<(f64, f64, f64)>, (f64, f64)) {
+    let radius_for = |n_group_territories: usize| 40.0 + 18.0 * n_group_territories as f64;
+    let max_radius = groups.iter().map(|(_, territory_indices)| radius_for(territory_indices.len())).fold(0.0, f64::max);
+    let cell_size = 2.0 * max_radius + 4.0 * NODE_RADIUS + 60.0;
+    let n_columns = (groups.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let n_rows = groups.len().div_ceil(n_columns).max(1);
+
+    let mut positions = vec![(0.0, 0.0); n_territories];
+    let mut circles = Vec::new();
+    for (group_index, (_, territory_indices)) in groups.iter().enumerate() {
+        let center_x = cell_size * ((group_index % n_columns) as f64 + 0.5);
+        let center_y = cell_size * ((group_index / n_columns) as f64 + 0.5);
+        let radius = radius_for(territory_indices.len());
+        for (i, territory_index) in territory_indices.iter().enumerate() {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / territory_indices.len() as f64;
+            positions[*territory_index as usize] = (center_x + radius * angle.sin(), center_y - radius * angle.cos());
+        }
+        circles.push((center_x, center_y, radius + 2.0 * NODE_RADIUS));
+    }
+    (positions, circles, (cell_size * n_columns as f64, cell_size * n_rows as f64))
+}
+
+pub fn to_svg(map: &Map, players: &[Player], show_armies: bool) -> String {
+    let territories = &map.territories;
+    let groups = territory_groups(territories, &map.continents);
+    let (positions, circles, (width, height)) = match map_layout(map) {
+        Some((positions, size)) => (positions, Vec::new(), size),
+        None => circle_layout(&groups, territories.node_count()),
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" font-family=\"sans-serif\" font-size=\"12\">\n",
+        width, height);
+    svg.push_str(&format!("<rect width=\"{:.0}\" height=\"{:.0}\" fill=\"#f4f1e8\"/>\n", width, height));
+
+    for ((continent_name, _), (center_x, center_y, radius)) in groups.iter().zip(circles.iter()) {
+        if let Some(continent_name) = continent_name {
+            svg.push_str(&format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"#e3ddc8\" stroke=\"#b8ae8c\"/>\n",
+                center_x, center_y, radius));
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"16\" font-weight=\"bold\">{}</text>\n",
+                center_x, center_y - radius - 8.0, escape_xml(continent_name)));
+        }
+    }
+    // Without continent circles, continent names go above their territories.
+    if circles.is_empty() {
+        for (continent_name, territory_indices) in &groups {
+            if let (Some(continent_name), false) = (continent_name, territory_indices.is_empty()) {
+                let center_x = territory_indices.iter().map(|index| positions[*index as usize].0).sum::<f64>() / territory_indices.len() as f64;
+                let top_y = territory_indices.iter().map(|index| positions[*index as usize].1).fold(f64::MAX, f64::min);
+                svg.push_str(&format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"16\" font-weight=\"bold\" fill=\"#8a7f5c\">{}</text>\n",
+                    center_x, top_y - 2.0 * NODE_RADIUS, escape_xml(continent_name)));
+            }
+        }
+    }
+
+    // Sea borders are dashed, and one-way routes end in an arrow at the edge of
+    // the territory they lead to.
+    svg.push_str("<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\">\
+<path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#555555\"/></marker></defs>\n");
+    for edge in territories.edge_references() {
+        let (from, to) = border_ends(edge);
+        let (x1, y1) = positions[from];
+        let (mut x2, mut y2) = positions[to];
+        let mut style = String::new();
+        if edge.weight().kind == BorderKind::Sea {
+            style.push_str(" stroke-dasharray=\"6 4\"");
+        }
+        if edge.weight().one_way_from.is_some() {
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+            x2 -= (x2 - x1) * NODE_RADIUS / length;
+            y2 -= (y2 - y1) * NODE_RADIUS / length;
+            style.push_str(" marker-end=\"url(#arrow)\"");
+        }
+        svg.push_str(&format!(
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#555555\" stroke-width=\"2\"{}/>\n",
+            x1, y1, x2, y2, style));
+    }
+
+    for node_index in territories.node_indices() {
+        let territory_index = node_index.index() as u32;
+        let (x, y) = positions[node_index.index()];
+        svg.push_str(&format!(
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\" stroke=\"#222222\" stroke-width=\"2\"/>\n",
+            x, y, NODE_RADIUS, fill_color(players, territory_index)));
+        if let Some(armies) = army_count(players, territory_index, show_armies) {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-weight=\"bold\">{}</text>\n",
+                x, y + 4.0, armies));
+        }
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+            x, y + NODE_RADIUS + 14.0, escape_xml(territory_name(territories, territory_index))));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn boards_are_exported_with_owners_clusters_and_edges() {
+        let mut map = Map::default_map();
+        let territories = &map.territories;
+        let continents = &map.continents;
+        let mut players = vec![Player::new("Ann".to_string(), Color::Red)];
+        players[0].army_per_territory.insert(0, 7);
+
+        let dot = to_dot(&map, &players, true);
+        assert!(dot.contains("subgraph cluster_0 {\n        label=\"Australia\";"));
+        assert!(dot.contains("t0 [label=\"Western Australia\\n7\", fillcolor=\"#dc322f\"];"));
+        assert!(dot.contains("t1 [label=\"Eastern Australia\", fillcolor=\"#ffffff\"];"));
+        assert_eq!(dot.matches(" -- ").count(), territories.edge_count());
+        assert!(dot.contains("    t0 -- t1;\n    t0 -- t3 [style=dashed];\n"));
+
+        let svg = to_svg(&map, &players, false);
+        assert_eq!(svg.matches("<line ").count(), territories.edge_count());
+        assert_eq!(svg.matches("<circle ").count(), territories.node_count() + continents.len());
+        assert!(svg.contains("fill=\"#dc322f\""));
+        assert!(!svg.contains(">7</text>"));
+
+        // Once every territory has a position, territories are drawn there.
+        for territory_index in 0..map.positions.len() {
+            map.positions[territory_index] = Some((100.0 * territory_index as f64, 50.0));
+        }
+        assert!(to_dot(&map, &players, true).contains("fillcolor=\"#dc322f\", pos=\"0,-50\"];"));
+        let svg = to_svg(&map, &players, true);
+        assert_eq!(svg.matches("<circle ").count(), map.territories.node_count());
+        assert!(svg.contains("<circle cx=\"88.0\" cy=\"88.0\""));
+    }
+}
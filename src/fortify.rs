@@ -0,0 +1,196 @@
+// Fortify phase: at the end of their turn a player may move armies once between
+// two of their territories, through territories held by them or their teammates.
+
+use std::collections::VecDeque;
+
+use petgraph::graph::UnGraph;
+
+use crate::input::Input;
+use crate::rules::RuleSet;
+use crate::undo::{Action, History};
+use crate::{crossable_borders, teams, territory_owner, Border, BorderKind, Player, Territory};
+
+// Breadth-first search from one territory to another, only passing through
+// territories held by the player or their teammates, following one-way routes
+// only in their direction, and crossing the sea only if the rules allow it.
+fn is_connected_through_allies(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    player_idx: usize,
+    from_territory_index: u32,
+    to_territory_index: u32,
+    rules: &RuleSet) -> bool {
+    let mut visited = vec![false; territories.node_count()];
+    let mut queue = VecDeque::new();
+    visited[from_territory_index as usize] = true;
+    queue.push_back(from_territory_index);
+
+    while let Some(territory_index) = queue.pop_front() {
+        if territory_index == to_territory_index {
+            return true;
+        }
+        for (neighbor_index, border) in crossable_borders(territories, territory_index) {
+            if visited[neighbor_index as usize] || (border.kind == BorderKind::Sea && !rules.fortify_over_sea) {
+                continue;
+            }
+            let is_allied = territory_owner(players, neighbor_index)
+                .is_some_and(|owner_idx| teams::are_allies(players, player_idx, owner_idx));
+            if is_allied {
+                visited[neighbor_index as usize] = true;
+                queue.push_back(neighbor_index);
+            }
+        }
+    }
+    false
+}
+
+pub fn fortify(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    player_idx: usize,
+    rules: &RuleSet,
+    history: &mut History,
+    input: &mut Input) {
+    println!("==== Fortify phase ====");
+    loop {
+        let fortify_any = input.prompt_yes_no("fortify", "Do you want to move armies between your territories? (y/n, or redo): ");
+        match fortify_any.as_str() {
+            "y" | "Y" => {
+                if !move_armies(territories, players, player_idx, rules, history, input) {
+                    continue;
+                }
+            }
+            "n" | "N" => return,
+            "redo" => {
+                if history.redo(players) != Some(Action::Fortify) {
+                    input.reject("There is nothing to redo.");
+                    continue;
+                }
+                println!("Move redone.");
+            }
+            _ => {
+                println!("Invalid input, skipping fortify phase.");
+                return;
+            }
+        }
+
+        // The move ends the turn, unless the player takes it back first.
+        let take_back = input.prompt_yes_no("undo", "Do you want to take back this move? (y/n): ");
+        if !matches!(take_back.as_str(), "y" | "Y" | "undo") {
+            return;
+        }
+        history.undo(players);
+        println!("Move undone.");
+    }
+}
+
+// Asks for a single move and makes it. Returns false if the move is not allowed.
+fn move_armies(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    player_idx: usize,
+    rules: &RuleSet,
+    history: &mut History,
+    input: &mut Input) -> bool {
+    let player = &players[player_idx];
+    let mut sorted_territory_indices: Vec<u32> = player.army_per_territory.keys().cloned().collect();
+    sorted_territory_indices.sort();
+    println!("Select territory index to move armies from:");
+    for territory_index in sorted_territory_indices.iter() {
+        println!("Territory index: {}, territory name: {}, Armies: {}",
+            territory_index,
+            territories.node_weight(petgraph::graph::NodeIndex::new(*territory_index as usize)).unwrap().name,
+            player.army_per_territory[territory_index]);
+    }
+
+    let from_territory_index: u32 = input.prompt_number("fortify", "Moving armies from territory index: ");
+    let from_armies = match player.army_per_territory.get(&from_territory_index) {
+        Some(armies) if *armies > rules.armies_left_behind => *armies,
+        Some(_) => {
+            input.reject("Not enough armies to move from this territory.");
+            return false;
+        }
+        None => {
+            input.reject("You do not own this territory.");
+            return false;
+        }
+    };
+
+    let to_territory_index: u32 = input.prompt_number("fortify", "Moving armies to territory index: ");
+    if !player.army_per_territory.contains_key(&to_territory_index) || to_territory_index == from_territory_index {
+        input.reject("You can only move armies to another territory you own.");
+        return false;
+    }
+    if !is_connected_through_allies(territories, players, player_idx, from_territory_index, to_territory_index, rules) {
+        input.reject("These territories are not connected through territories held by you or your team.");
+        return false;
+    }
+
+    let max_movable_armies = from_armies - rules.armies_left_behind;
+    let mut n_movable_armies = input.prompt_number(
+        "fortify",
+        &format!("Choose number of armies to move (between 1 and {}): ", max_movable_armies));
+    if n_movable_armies > max_movable_armies {
+        n_movable_armies = max_movable_armies;
+        println!("Requested too many movable armies, reducing to {}", n_movable_armies);
+    }
+    if n_movable_armies == 0 {
+        n_movable_armies = 1;
+        println!("Cannot move zero armies, increasing to 1.");
+    }
+
+    let before = players.to_vec();
+    let player = &mut players[player_idx];
+    *player.army_per_territory.get_mut(&from_territory_index).unwrap() -= n_movable_armies;
+    *player.army_per_territory.get_mut(&to_territory_index).unwrap() += n_movable_armies;
+    println!("Player {} moved {} armies from {} to {}.",
+        player.name,
+        n_movable_armies,
+        territories.node_weight(petgraph::graph::NodeIndex::new(from_territory_index as usize)).unwrap().name,
+        territories.node_weight(petgraph::graph::NodeIndex::new(to_territory_index as usize)).unwrap().name);
+
+    history.record(Action::Fortify, before, players);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_territories, Color};
+
+    #[test]
+    fn fortify_paths_pass_through_teammates_but_not_enemies() {
+        let mut territories = setup_territories();
+        let mut rules = RuleSet::classic();
+        // Western Australia (0) - Indonesia (3) - Southeast Asia (13) - China (5)
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+        ];
+        players[0].army_per_territory.insert(0, 3);
+        players[0].army_per_territory.insert(5, 1);
+        players[1].army_per_territory.insert(3, 1);
+        players[1].army_per_territory.insert(13, 1);
+        for territory_index in [1, 2, 4, 6, 7, 8, 9, 10, 11, 12, 14, 15] {
+            players[2].army_per_territory.insert(territory_index, 1);
+        }
+        assert!(!is_connected_through_allies(&territories, &players, 0, 0, 5, &rules));
+
+        players[0].team = Some(1);
+        players[1].team = Some(1);
+        players[2].team = Some(2);
+        assert!(is_connected_through_allies(&territories, &players, 0, 0, 5, &rules));
+        assert!(!is_connected_through_allies(&territories, &players, 2, 1, 5, &rules));
+
+        // One-way routes are only followed in their direction.
+        let edge = territories.find_edge(petgraph::graph::NodeIndex::new(13), petgraph::graph::NodeIndex::new(5)).unwrap();
+        territories[edge].one_way_from = Some(5);
+        assert!(!is_connected_through_allies(&territories, &players, 0, 0, 5, &rules));
+        assert!(is_connected_through_allies(&territories, &players, 0, 5, 0, &rules));
+
+        // Western Australia borders Indonesia across the sea.
+        rules.fortify_over_sea = false;
+        assert!(!is_connected_through_allies(&territories, &players, 0, 5, 0, &rules));
+    }
+}
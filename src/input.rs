@@ -0,0 +1,300 @@
+// Source of player input. During normal play this reads from stdin, while tests
+// feed it canned lines so that interactive phases can be driven automatically.
+//
+// With "--script <file>" every decision is instead read from a script of
+// labelled commands, e.g. "attack 5 12 blitz" or "fortify 3 5 4". Each prompt
+// names the command that answers it: yes/no prompts are answered "y" by that
+// command and "n" by "end", and the command's arguments then answer the prompts
+// that follow, in order. Any command or input the game does not accept aborts
+// the game with the offending script line. Where the game allows it, "undo" and
+// "redo" are given as commands of their own.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::fmt::Debug;
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+
+// Words that switch on an option for the whole command rather than answering a
+// prompt, e.g. "blitz" keeps an attack going until it ends.
+const FLAGS: [&str; 1] = ["blitz"];
+
+const HISTORY_COMMANDS: [&str; 2] = ["undo", "redo"];
+
+// The script command currently answering prompts.
+struct Command {
+    name: String,
+    args: VecDeque<String>,
+    flags: Vec<String>,
+}
+
+pub struct Input {
+    reader: Box<dyn BufRead>,
+    is_script: bool,
+    line_number: usize,
+    line: String,
+    command: Option<Command>,
+}
+
+impl Input {
+    pub fn stdin() -> Self {
+        Input::new(Box::new(io::stdin().lock()), false)
+    }
+
+    pub fn script(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|error| format!("{}: {}", path, error))?;
+        Ok(Input::new(Box::new(BufReader::new(file)), true))
+    }
+
+    #[cfg(test)]
+    pub fn from_lines(lines: &[&str]) -> Self {
+        Input::new(Box::new(io::Cursor::new(Input::join_lines(lines))), false)
+    }
+
+    #[cfg(test)]
+    pub fn script_from_lines(lines: &[&str]) -> Self {
+        Input::new(Box::new(io::Cursor::new(Input::join_lines(lines))), true)
+    }
+
+    #[cfg(test)]
+    fn join_lines(lines: &[&str]) -> String {
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        contents
+    }
+
+    fn new(reader: Box<dyn BufRead>, is_script: bool) -> Self {
+        Input { reader, is_script, line_number: 0, line: String::new(), command: None }
+    }
+
+    // Prints the prompt, flushing stdout so that it appears before we block on
+    // input, and returns the trimmed line entered by the player. In script mode
+    // the remaining arguments of the command answer the prompt.
+    pub fn prompt(&mut self, command: &str, prompt: &str) -> String {
+        print!("{}", prompt);
+        if !self.is_script {
+            io::stdout().flush().expect("Failed to flush stdout");
+            return self.read_line();
+        }
+
+        let answer = match self.start_command(command) {
+            Some(history_command) => history_command,
+            None => {
+                let args: Vec<String> = self.command.as_mut().unwrap().args.drain(..).collect();
+                args.join(" ")
+            }
+        };
+        println!("{}", answer);
+        answer
+    }
+
+    // Asks for a number. Players are asked again until they type one, and are
+    // told where they can undo or redo if they try to here.
+    pub fn prompt_number<T: FromStr>(&mut self, command: &str, prompt: &str) -> T
+    where
+        T::Err: Debug,
+    {
+        if !self.is_script {
+            loop {
+                let answer = self.prompt(command, prompt);
+                match answer.parse() {
+                    Ok(number) => return number,
+                    Err(_) if HISTORY_COMMANDS.contains(&answer.as_str()) => println!(
+                        "Please finish this choice first: {} is offered at the next question that mentions it.",
+                        answer),
+                    Err(_) => println!("\"{}\" is not a number, please try again.", answer),
+                }
+            }
+        }
+
+        print!("{}", prompt);
+        if self.start_command(command).is_some() {
+            self.fail("cannot undo or redo here");
+        }
+        let arg = self.command.as_mut().unwrap().args.pop_front().unwrap();
+        println!("{}", arg);
+        match arg.parse() {
+            Ok(number) => number,
+            Err(_) => self.fail(&format!("\"{}\" is not a number", arg)),
+        }
+    }
+
+    // Asks a yes/no question. In script mode the answer is "y" if the next
+    // command is the given one, "n" if it is "end", and "undo" or "redo" if it
+    // is one of those.
+    pub fn prompt_yes_no(&mut self, command: &str, prompt: &str) -> String {
+        if !self.is_script {
+            return self.prompt(command, prompt);
+        }
+
+        print!("{}", prompt);
+        self.finish_command();
+        let next_command = self.read_command();
+        let answer = if next_command.name == command {
+            self.command = Some(next_command);
+            "y".to_string()
+        } else if next_command.name == "end" && next_command.args.is_empty() {
+            "n".to_string()
+        } else if HISTORY_COMMANDS.contains(&next_command.name.as_str()) && next_command.args.is_empty() {
+            next_command.name
+        } else {
+            self.fail(&format!("expected \"{}\" or \"end\"", command))
+        };
+        println!("{}", answer);
+        answer
+    }
+
+    // Asks a yes/no question that a script answers with a flag on the current
+    // command, e.g. "attack 5 12 blitz" keeps attacking until the attack ends.
+    pub fn prompt_flag(&mut self, command: &str, flag: &str, prompt: &str) -> String {
+        if !self.is_script {
+            return self.prompt(command, prompt);
+        }
+
+        print!("{}", prompt);
+        let has_flag = self.command
+            .as_ref()
+            .is_some_and(|current| current.name == command && current.flags.iter().any(|seen| seen == flag));
+        let answer = if has_flag { "y" } else { "n" };
+        println!("{}", answer);
+        answer.to_string()
+    }
+
+    // Reports input the game does not accept. Players are asked again, while a
+    // script is aborted.
+    pub fn reject(&self, message: &str) {
+        if self.is_script {
+            self.fail(message);
+        }
+        println!("{}", message);
+    }
+
+    pub fn read_line(&mut self) -> String {
+        let mut line = String::new();
+        let n_bytes = self.reader
+            .read_line(&mut line)
+            .expect("Failed to read line");
+        // Without this check we would keep reading empty lines, and e.g. skip
+        // every attack phase forever.
+        if n_bytes == 0 {
+            panic!("Unexpected end of input");
+        }
+        line.trim().to_string()
+    }
+
+    // Makes sure the given command is answering prompts and has an argument
+    // left, reading the next command if the current one is used up. Returns the
+    // command instead if it is "undo" or "redo".
+    fn start_command(&mut self, command: &str) -> Option<String> {
+        let has_arg = self.command
+            .as_ref()
+            .is_some_and(|current| current.name == command && !current.args.is_empty());
+        if has_arg {
+            return None;
+        }
+
+        self.finish_command();
+        let next_command = self.read_command();
+        if HISTORY_COMMANDS.contains(&next_command.name.as_str()) && next_command.args.is_empty() {
+            return Some(next_command.name);
+        }
+        if next_command.name != command {
+            self.fail(&format!("expected \"{}\"", command));
+        }
+        if next_command.args.is_empty() {
+            self.fail(&format!("\"{}\" needs more arguments", command));
+        }
+        self.command = Some(next_command);
+        None
+    }
+
+    fn finish_command(&mut self) {
+        if let Some(current) = self.command.take() {
+            if !current.args.is_empty() {
+                self.fail(&format!("unexpected arguments for \"{}\"", current.name));
+            }
+        }
+    }
+
+    // Reads the next command, skipping blank lines and "#" comments.
+    fn read_command(&mut self) -> Command {
+        loop {
+            let mut line = String::new();
+            let n_bytes = self.reader
+                .read_line(&mut line)
+                .expect("Failed to read line");
+            if n_bytes == 0 {
+                panic!("Unexpected end of script after line {}", self.line_number);
+            }
+            self.line_number += 1;
+            self.line = line.trim().to_string();
+
+            let mut words = self.line.split_whitespace().map(|word| word.to_string());
+            match words.next() {
+                Some(name) if !name.starts_with('#') => {
+                    let (flags, args): (Vec<String>, Vec<String>) = words.partition(|word| FLAGS.contains(&word.as_str()));
+                    return Command { name, args: args.into(), flags };
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    fn fail(&self, message: &str) -> ! {
+        println!();
+        panic!("Script line {} (\"{}\"): {}", self.line_number, self.line, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_commands_answer_prompts_in_order() {
+        let mut input = Input::script_from_lines(&[
+            "# Ann attacks twice, then fortifies",
+            "attack 5 12 blitz",
+            "",
+            "end",
+            "fortify 3 5 4",
+            "name Ann Smith",
+        ]);
+        assert_eq!(input.prompt_yes_no("attack", ""), "y");
+        assert_eq!(input.prompt_number::<u32>("attack", ""), 5);
+        assert_eq!(input.prompt_number::<u32>("attack", ""), 12);
+        assert_eq!(input.prompt_flag("attack", "blitz", ""), "y");
+        assert_eq!(input.prompt_flag("attack", "blitz", ""), "y");
+        assert_eq!(input.prompt_yes_no("attack", ""), "n");
+        assert_eq!(input.prompt_flag("attack", "blitz", ""), "n");
+        assert_eq!(input.prompt_yes_no("fortify", ""), "y");
+        assert_eq!(input.prompt_number::<u32>("fortify", ""), 3);
+        assert_eq!(input.prompt_number::<u32>("fortify", ""), 5);
+        assert_eq!(input.prompt_number::<u32>("fortify", ""), 4);
+        assert_eq!(input.prompt("name", ""), "Ann Smith");
+    }
+
+    #[test]
+    fn players_are_asked_again_until_they_type_a_number() {
+        let mut input = Input::from_lines(&["undo", "five", "", "5"]);
+        assert_eq!(input.prompt_number::<u32>("attack", ""), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Script line 1 (\"fortify 3 5 4\"): expected \"attack\" or \"end\"")]
+    fn scripts_fail_on_unexpected_commands() {
+        let mut input = Input::script_from_lines(&["fortify 3 5 4"]);
+        input.prompt_yes_no("attack", "");
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected arguments for \"attack\"")]
+    fn scripts_fail_on_unused_arguments() {
+        let mut input = Input::script_from_lines(&["attack 5 12 13", "end"]);
+        input.prompt_yes_no("attack", "");
+        input.prompt_number::<u32>("attack", "");
+        input.prompt_number::<u32>("attack", "");
+        input.prompt_yes_no("attack", "");
+    }
+}
@@ -1,27 +1,233 @@
 // Implementation of a Risk-like turn-based strategy game in Rust.
 
-use std::io;
-use std::io::{Write}; // Import the Write trait for flushing stdout
+mod campaign;
+mod capitals;
+mod cli;
+mod combat;
+mod diplomacy;
+mod economy;
+mod export;
+mod fortify;
+mod input;
+mod map;
+mod map_check;
+mod map_editor;
+mod map_generator;
+mod missions;
+mod puzzle;
+mod rules;
+mod scenario;
+mod scoring;
+mod setup;
+mod teams;
+mod undo;
+
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use petgraph::graph::UnGraph; // For use in graph representation of the world map
-use petgraph::visit::IntoNodeReferences;
-use rand::Rng;
-use rand::prelude::SliceRandom;
-
-#[derive(Clone, Debug)]
+use petgraph::visit::{EdgeRef, IntoNodeReferences};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use campaign::Campaign;
+use cli::{Options, Subcommand};
+use combat::{perform_attack, Dice, LoadedDice, RandomDice};
+use diplomacy::Diplomacy;
+use economy::Building;
+use input::Input;
+use map::Map;
+use missions::{Mission, MissionDeck};
+use puzzle::Puzzle;
+use rules::{GameMode, RuleSet};
+use scenario::Scenario;
+use setup::{add_neutral_player, assign_territories_and_armies_to_players};
+use undo::{Action, History};
+
+#[derive(Clone, Debug, PartialEq)]
 enum Color {
     Red,
     Blue,
     Green,
     Yellow,
     Indigo,
+    Orange,
+    Pink,
+    Cyan,
+    Grey, // Reserved for the neutral player
+}
+
+// One colour per player, in the order they are handed out when players do not
+// choose their own.
+const MAX_PLAYERS: usize = 8;
+const PLAYER_COLORS: [Color; MAX_PLAYERS] = [
+    Color::Red,
+    Color::Blue,
+    Color::Green,
+    Color::Yellow,
+    Color::Indigo,
+    Color::Orange,
+    Color::Pink,
+    Color::Cyan,
+];
+
+impl Color {
+    fn from_name(name: &str) -> Option<Color> {
+        PLAYER_COLORS
+            .iter()
+            .chain([Color::Grey].iter())
+            .find(|color| color.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Color::Red => "Red",
+            Color::Blue => "Blue",
+            Color::Green => "Green",
+            Color::Yellow => "Yellow",
+            Color::Indigo => "Indigo",
+            Color::Orange => "Orange",
+            Color::Pink => "Pink",
+            Color::Cyan => "Cyan",
+            Color::Grey => "Grey",
+        }
+    }
+
+    fn rgb(&self) -> (u8, u8, u8) {
+        match self {
+            Color::Red => (220, 50, 47),
+            Color::Blue => (38, 110, 220),
+            Color::Green => (60, 170, 60),
+            Color::Yellow => (230, 200, 30),
+            Color::Indigo => (95, 60, 180),
+            Color::Orange => (240, 130, 30),
+            Color::Pink => (230, 110, 180),
+            Color::Cyan => (40, 190, 200),
+            Color::Grey => (140, 140, 140),
+        }
+    }
+
+    // Wraps the text in ANSI escape codes so that terminals show it in this colour.
+    fn paint(&self, text: &str) -> String {
+        let (red, green, blue) = self.rgb();
+        format!("\x1b[38;2;{};{};{}m{}\x1b[0m", red, green, blue, text)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BorderKind {
+    Land,
+    Sea,
+}
+
+// A border between two territories, which can be crossed both ways unless it is
+// a one-way route.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Border {
+    kind: BorderKind,
+    one_way_from: Option<u32>, // Territory index of the only end it can be crossed from
+}
+
+impl Border {
+    fn land() -> Self {
+        Border { kind: BorderKind::Land, one_way_from: None }
+    }
+
+    fn sea() -> Self {
+        Border { kind: BorderKind::Sea, one_way_from: None }
+    }
+
+    fn can_cross_from(&self, territory_index: u32) -> bool {
+        self.one_way_from.is_none_or(|from| from == territory_index)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Terrain {
+    Plains,
+    Mountains,
+    Urban,
+}
+
+impl Terrain {
+    fn from_name(name: &str) -> Option<Terrain> {
+        [Terrain::Plains, Terrain::Mountains, Terrain::Urban]
+            .into_iter()
+            .find(|terrain| terrain.name() == name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Terrain::Plains => "plains",
+            Terrain::Mountains => "mountains",
+            Terrain::Urban => "urban",
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
+struct Territory {
+    name: String,
+    terrain: Terrain,
+    fortress: bool,
+    production: u32, // Counts instead of the territory itself when reinforcing by production
+    // Left on the map by campaign winners, and held by whoever holds the
+    // territory: a city works like one bought with gold, and a bunker makes the
+    // defender's highest die count more.
+    city: bool,
+    bunker: bool,
+}
+
+impl Territory {
+    fn new(name: &str) -> Self {
+        Territory { name: name.to_string(), terrain: Terrain::Plains, fortress: false, production: 1, city: false, bunker: false }
+    }
+
+    // Lists what sets the territory apart from plain territories, e.g.
+    // "mountains, fortress, production 2", or nothing for a plain territory.
+    fn features(&self) -> Vec<String> {
+        let mut features = Vec::new();
+        if self.terrain != Terrain::Plains {
+            features.push(self.terrain.name().to_string());
+        }
+        if self.fortress {
+            features.push("fortress".to_string());
+        }
+        if self.production != 1 {
+            features.push(format!("production {}", self.production));
+        }
+        if self.city {
+            features.push("city".to_string());
+        }
+        if self.bunker {
+            features.push("bunker".to_string());
+        }
+        features
+    }
+}
+
+struct Continent {
+    name: String,
+    bonus: u32, // What holding the whole continent is worth
+    territories: Vec<u32>, // Territory indices
+}
+
+#[derive(Clone, Debug)]
 struct Player {
     name: String,
     color: Color,
     army_per_territory: HashMap<u32,u32>, // Mapping of territory index to number of armies
+    // A neutral player holds territories and defends them, but never takes a
+    // turn, and is ignored when checking for elimination and victory.
+    is_neutral: bool,
+    // Secret mission, only dealt when playing in mission mode.
+    mission: Option<Mission>,
+    // Territory index of the player's capital, only chosen in capital conquest mode.
+    capital: Option<u32>,
+    // Players with the same team number are allies, players without a team play alone.
+    team: Option<u32>,
+    // Gold, and buildings by territory index, only used with the economy rules.
+    gold: u32,
+    buildings: HashMap<u32, Vec<Building>>,
 }
 
 impl Player {
@@ -30,571 +236,748 @@ impl Player {
             name,
             color,
             army_per_territory: HashMap::new(),
+            is_neutral: false,
+            mission: None,
+            capital: None,
+            team: None,
+            gold: 0,
+            buildings: HashMap::new(),
         }
     }
-}
-
-fn setup_players(names: Vec<String>) -> Vec<Player> {
-    let colors = vec![
-        Color::Red,
-        Color::Blue,
-        Color::Green,
-        Color::Yellow,
-        Color::Indigo,
-    ];
-
-    let mut players = Vec::new();
-    for (i, name) in names.into_iter().enumerate() {
-        let color = colors.get(i).unwrap_or(&Color::Red).clone(); // Default to Red if out of colors
 
-        // We use player.color in the print statement to avoid a compiler warning
-        // and the color field never being read.
-        let player = Player::new(name, color);
-        println!("{} has been assigned color {:?}", player.name, player.color);
-        players.push(player);
+    fn neutral() -> Self {
+        Player {
+            is_neutral: true,
+            ..Player::new("Neutral".to_string(), Color::Grey)
+        }
     }
 
-    players
+    fn total_armies(&self) -> u32 {
+        self.army_per_territory.values().sum()
+    }
 }
 
-fn assign_territories_and_armies_to_players(
-    territories: &UnGraph<&'static str, ()>,
-    players: &mut Vec<Player>) {
-    let mut territory_indices: Vec<u32> = territories
-        .node_indices()
-        .map(|index| index.index() as u32)
-        .collect();
-
-    // Randomly permute territory_indices so that we assign territories to players in
-    // a random manner.
-    let mut rng = rand::thread_rng();
-    territory_indices.shuffle(&mut rng);
-
-    let mut player_index = 0;
-    for territory_index in territory_indices {
-        players[player_index].army_per_territory.insert(territory_index, 0); // Start with 0 armies
-        player_index = (player_index + 1) % players.len();
-    }
-
-    // Now assign armies to each territory
-    let armies_per_player =
-        match players.len() {
-            1 => 45,
-            2 => 40,
-            3 => 35,
-            4 => 30,
-            5 => 25,
-            _ => 0, // This case should not occur due to earlier checks
-        };
-
-    println!("Do you want to manually assign armies, or automatically assign armies to all territories evenly?");
-    print!("Type 1 for manual, or 2 for automatic even assignment: ");
-
-    io::stdout().flush().expect("Failed to flush stdout");
+fn prompt_players(input: &mut Input) -> Vec<(String, Option<Color>)> {
+    let number_of_players: usize = loop {
+        let number_of_players = input.prompt_number(
+            "players",
+            &format!("Please enter the number of players between 1 and {}: ", MAX_PLAYERS));
+        if (1..=MAX_PLAYERS).contains(&number_of_players) {
+            break number_of_players;
+        }
+        input.reject(&format!("Number of players must be between 1 and {}.", MAX_PLAYERS));
+    };
 
-    let mut manual_or_even_assignment = String::new();
-    io::stdin()
-        .read_line(&mut manual_or_even_assignment)
-        .expect("Failed to read line");
-    let manual_or_even_assignment = manual_or_even_assignment.trim().parse().expect("Please type a number!");
+    let mut players: Vec<(String, Option<Color>)> = Vec::new();
+    for i in 0..number_of_players {
+        let name = input.prompt("name", &format!("Enter name for Player {}: ", i + 1));
+        let taken_colors: Vec<Color> = players.iter().filter_map(|(_, color)| color.clone()).collect();
+        let color = prompt_color(&name, &taken_colors, input);
+        players.push((name, Some(color)));
+    }
+    println!();
+    players
+}
 
-    let mut is_manual_assignment = false;
-    match manual_or_even_assignment {
-        1 => {
-            is_manual_assignment = true;
-            println!("Manual assignment mode selected, players will assign {} armies to territories.", armies_per_player);
-        },
-        2 => {
-            println!("Automatic even assignment mode selected.");
-        },
-        _ => {
-            println!("Invalid input. Defaulting to automatic even assignment.");
+// Asks for a colour nobody else has taken yet, suggesting the first free one.
+fn prompt_color(name: &str, taken_colors: &[Color], input: &mut Input) -> Color {
+    let free_colors: Vec<&Color> = PLAYER_COLORS.iter().filter(|color| !taken_colors.contains(color)).collect();
+    let free_color_names: Vec<&str> = free_colors.iter().map(|color| color.name()).collect();
+    loop {
+        let answer = input.prompt(
+            "color",
+            &format!("Choose a colour for {} ({}), or press enter for {}: ",
+                name,
+                free_color_names.join(", "),
+                free_colors[0].name()));
+        if answer.is_empty() {
+            return free_colors[0].clone();
+        }
+        match Color::from_name(&answer) {
+            Some(color) if free_colors.contains(&&color) => return color,
+            Some(color) => input.reject(&format!("{} is not available, please choose another colour.", color.name())),
+            None => input.reject(&format!("Unknown colour \"{}\", please choose again.", answer)),
         }
     }
+}
 
-    if is_manual_assignment {
-        let mut army_count_per_player = vec![0; players.len()];
+// Players who did not choose a colour get the first one nobody else has.
+fn setup_players(players_and_colors: Vec<(String, Option<Color>)>) -> Vec<Player> {
+    let mut taken_colors: Vec<Color> = players_and_colors.iter().filter_map(|(_, color)| color.clone()).collect();
 
-        // Add one troop to each territory
-        for player_index in 0..players.len() {
-            let player = &mut players[player_index];
-            let territory_indices = player.army_per_territory.keys().cloned().collect::<Vec<u32>>();
-            for territory_index in territory_indices {
-                player.army_per_territory.insert(territory_index, 1);
-                army_count_per_player[player_index] += 1;
-            }
-        }
+    let mut players = Vec::new();
+    for (name, color) in players_and_colors {
+        let color = color.unwrap_or_else(|| {
+            let free_color = PLAYER_COLORS.iter().find(|color| !taken_colors.contains(color)).unwrap().clone();
+            taken_colors.push(free_color.clone());
+            free_color
+        });
 
-        'outer_loop: loop {
-            let mut player_index = 0;
-            loop {
-                // We only increment player_index once an army is successfully added.
-                if player_index >= players.len() {
-                    break;
-                }
+        let player = Player::new(name, color);
+        println!("{} plays as {}", player.color.paint(&player.name), player.color.name());
+        players.push(player);
+    }
 
-                let player = &mut players[player_index];
-                let mut sorted_territory_indices = Vec::new();
-                for territory_index in player.army_per_territory.keys() {
-                    sorted_territory_indices.push(*territory_index);
-                }
-                sorted_territory_indices.sort();
-
-                // Print the current armies per territory
-                println!("\nPlayer: {}, current territories:", player.name);
-                for territory_index in sorted_territory_indices {
-                    let territory_name = territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap();
-                    let armies = player.army_per_territory.get(&territory_index).unwrap();
-                    println!("Territory index: {}, territory name: {}, Armies: {}", territory_index, territory_name, armies);
-                }
+    players
+}
 
-                print!("Choose a territory index to add an army: ");
-                io::stdout().flush().expect("Failed to flush stdout");
+// Returns the index of the player who owns the given territory, if any.
+fn territory_owner(players: &[Player], territory_index: u32) -> Option<usize> {
+    players
+        .iter()
+        .position(|player| player.army_per_territory.contains_key(&territory_index))
+}
 
-                let mut selected_index = String::new();
-                io::stdin()
-                    .read_line(&mut selected_index)
-                    .expect("Failed to read line");
-                // Reuse the selected_index variable name (requires us to re-declare with "let")
-                let selected_index = selected_index.trim().parse().expect("Please type a number!");
+// With fog of war enabled, a player only sees exact army counts on territories
+// held by them or their teammates, and on enemy territories adjacent to those.
+// Output with no viewer (e.g. shown to all players at once) reveals no army
+// counts under fog.
+fn is_army_count_visible(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    viewer: Option<usize>,
+    fog_of_war: bool,
+    territory_index: u32) -> bool {
+    if !fog_of_war {
+        return true;
+    }
 
-                if let Some(armies) = player.army_per_territory.get_mut(&selected_index) {
-                    *armies += 1;
-                    army_count_per_player[player_index] += 1;
+    let viewer = match viewer {
+        Some(viewer) => viewer,
+        None => return false,
+    };
+
+    let is_allied_territory = |territory_index: u32| {
+        territory_owner(players, territory_index)
+            .is_some_and(|owner_idx| teams::are_allies(players, viewer, owner_idx))
+    };
+    if is_allied_territory(territory_index) {
+        return true;
+    }
 
-                    let territory_name = territories.node_weight(petgraph::graph::NodeIndex::new(selected_index as usize)).unwrap();
-                    println!("Player {} now has {} armies in {}.", player.name, *armies, territory_name);
+    territories
+        .neighbors(petgraph::graph::NodeIndex::new(territory_index as usize))
+        .any(|neighbor| is_allied_territory(neighbor.index() as u32))
+}
 
-                    if army_count_per_player[player_index] >= armies_per_player {
-                        println!("Player {} has assigned all their armies.", player.name);
-                        break 'outer_loop;
-                    }
+// Formats the army count of a territory as seen by the viewer, or "?" if it is
+// hidden by fog of war.
+fn visible_army_count(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    viewer: Option<usize>,
+    fog_of_war: bool,
+    owner_idx: usize,
+    territory_index: u32) -> String {
+    if is_army_count_visible(territories, players, viewer, fog_of_war, territory_index) {
+        players[owner_idx].army_per_territory.get(&territory_index).unwrap().to_string()
+    } else {
+        "?".to_string()
+    }
+}
 
-                    player_index += 1;
-                } else {
-                    println!("You do not own this territory, please select again.");
-                    continue;
-                }
-            }
-        }
-        for player in players.iter_mut() {
-            let mut total_armies = 0;
-            'player_loop: loop {
-                for (_territory_index, armies) in player.army_per_territory.iter_mut() {
-                    // We need to check if we've already assigned enough armies since
-                    // we iterate over all territories
-                    if total_armies >= armies_per_player {
-                        break 'player_loop;
-                    }
+fn print_players(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    viewer: Option<usize>,
+    fog_of_war: bool) {
+    for player_idx in 0..players.len() {
+        print_player(territories, players, player_idx, viewer, fog_of_war);
+    }
+}
 
-                    *armies += 1;
-                    total_armies += 1;
-                }
-            }
-        }
-        // for player in players.iter_mut() {
-        //     println!("Player: {}", player.name);
-        //     for (territory_index, _) in player.army_per_territory.iter() {
-        //         let territory_name = territories.node_weight(petgraph::graph::NodeIndex::new(*territory_index as usize)).unwrap();
-        //         print!("Enter number of armies to place in {} (between 1 and 5): ", territory_name);
-        //         io::stdout().flush().expect("Failed to flush stdout");
-        //         let mut n_armies_input = String::new();
-        //         io::stdin()
-        //             .read_line(&mut n_armies_input)
-        //             .expect("Failed to read line");
-        //         let n_armies = n_armies_input.trim().parse().expect("Please type a number!");
-        //         player.army_per_territory.insert(*territory_index, n_armies);
-        //     }
-        // }
-    }
-    else {
-        for player in players.iter_mut() {
-            let mut total_armies = 0;
-            'player_loop: loop {
-                for (_territory_index, armies) in player.army_per_territory.iter_mut() {
-                    // We need to check if we've already assigned enough armies since
-                    // we iterate over all territories
-                    if total_armies >= armies_per_player {
-                        break 'player_loop;
-                    }
+fn print_player(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    player_idx: usize,
+    viewer: Option<usize>,
+    fog_of_war: bool) {
+    let player = &players[player_idx];
+    match player.team {
+        Some(team) => println!("Player: {} (Team {})", player.color.paint(&player.name), team),
+        None => println!("Player: {}", player.color.paint(&player.name)),
+    }
+    if player.gold > 0 || !player.buildings.is_empty() {
+        println!("  Gold: {}", player.gold);
+    }
+    for territory_index in player.army_per_territory.keys() {
+        let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(*territory_index as usize)).unwrap().name;
+        let armies = visible_army_count(territories, players, viewer, fog_of_war, player_idx, *territory_index);
+        println!("  Territory: {}{}, Armies: {}{}",
+            territory_name,
+            capitals::capital_marker(players, *territory_index),
+            armies,
+            describe_buildings(player, *territory_index));
+    }
+    println!();
+}
 
-                    *armies += 1;
-                    total_armies += 1;
-                }
-            }
+// Lists the buildings the player has on the territory, e.g. ", Buildings: city".
+fn describe_buildings(player: &Player, territory_index: u32) -> String {
+    match player.buildings.get(&territory_index) {
+        Some(buildings) if !buildings.is_empty() => {
+            let names: Vec<&str> = buildings.iter().map(|building| building.name()).collect();
+            format!(", Buildings: {}", names.join(", "))
         }
+        _ => String::new(),
     }
-
-    println!("\nTerritories and armies have been assigned to players as follows:");
-    print_players(&territories, players);
 }
 
-fn print_players(territories: &UnGraph<&'static str, ()>, players: &Vec<Player>) {
-    for player in players.iter() {
-        print_player(territories, player);
-    }
-}
-
-fn print_player(territories: &UnGraph<&'static str, ()>, player: &Player) {
-    println!("Player: {}", player.name);
-    for (territory_index, armies) in player.army_per_territory.iter() {
-        let territory_name = territories.node_weight(petgraph::graph::NodeIndex::new(*territory_index as usize)).unwrap();
-        println!("  Territory: {}, Armies: {}", territory_name, armies);
-    }
-    println!("");
-}
-
-fn setup_territories() -> UnGraph<&'static str, ()> {
-    let mut territories = UnGraph::<&str, ()>::new_undirected();
-
-    let aus_wa = territories.add_node("Western Australia");
-    let aus_ea = territories.add_node("Eastern Australia");
-    let aus_ng = territories.add_node("New Guinea");
-    let aus_id = territories.add_node("Indonesia");
-
-    territories.add_edge(aus_wa, aus_ea, ());
-    territories.add_edge(aus_wa, aus_id, ());
-    territories.add_edge(aus_ea, aus_ng, ());
-    territories.add_edge(aus_ng, aus_id, ());
-
-    let asia_in = territories.add_node("India");
-    let asia_ch = territories.add_node("China");
-    let asia_si = territories.add_node("Siberia");
-    let asia_mo = territories.add_node("Mongolia");
-    let asia_ja = territories.add_node("Japan");
-    let asia_ya = territories.add_node("Yakutsk");
-    let asia_ir = territories.add_node("Irkutsk");
-    let asia_af = territories.add_node("Afghanistan");
-    let asia_me = territories.add_node("Middle East");
-    let asia_se = territories.add_node("Southeast Asia");
-    let asia_ka = territories.add_node("Kamchatka");
-    let asia_ur = territories.add_node("Ural");
-
-    territories.add_edge(aus_id, asia_se, ());
-    territories.add_edge(asia_se, asia_ch, ());
-    territories.add_edge(asia_se, asia_in, ());
-    territories.add_edge(asia_in, asia_af, ());
-    territories.add_edge(asia_in, asia_me, ());
-    territories.add_edge(asia_in, asia_ch, ());
-    territories.add_edge(asia_me, asia_af, ());
-    territories.add_edge(asia_ch, asia_af, ());
-    territories.add_edge(asia_ch, asia_ur, ());
-    territories.add_edge(asia_ch, asia_si, ());
-    territories.add_edge(asia_ch, asia_mo, ());
-    territories.add_edge(asia_af, asia_ur, ());
-    territories.add_edge(asia_ja, asia_mo, ());
-    territories.add_edge(asia_ja, asia_ka, ());
-    territories.add_edge(asia_mo, asia_si, ());
-    territories.add_edge(asia_mo, asia_ir, ());
-    territories.add_edge(asia_mo, asia_ka, ());
-    territories.add_edge(asia_si, asia_ya, ());
-    territories.add_edge(asia_si, asia_ur, ());
-    territories.add_edge(asia_si, asia_ir, ());
-    territories.add_edge(asia_ka, asia_ir, ());
-    territories.add_edge(asia_ka, asia_ya, ());
-    territories.add_edge(asia_ya, asia_ir, ());
+fn setup_territories() -> UnGraph<Territory, Border> {
+    let mut territories = UnGraph::<Territory, Border>::new_undirected();
+
+    let aus_wa = territories.add_node(Territory::new("Western Australia"));
+    let aus_ea = territories.add_node(Territory::new("Eastern Australia"));
+    let aus_ng = territories.add_node(Territory::new("New Guinea"));
+    let aus_id = territories.add_node(Territory::new("Indonesia"));
+
+    territories.add_edge(aus_wa, aus_ea, Border::land());
+    territories.add_edge(aus_wa, aus_id, Border::sea());
+    territories.add_edge(aus_ea, aus_ng, Border::sea());
+    territories.add_edge(aus_ng, aus_id, Border::sea());
+
+    let asia_in = territories.add_node(Territory::new("India"));
+    let asia_ch = territories.add_node(Territory::new("China"));
+    let asia_si = territories.add_node(Territory::new("Siberia"));
+    let asia_mo = territories.add_node(Territory::new("Mongolia"));
+    let asia_ja = territories.add_node(Territory::new("Japan"));
+    let asia_ya = territories.add_node(Territory::new("Yakutsk"));
+    let asia_ir = territories.add_node(Territory::new("Irkutsk"));
+    let asia_af = territories.add_node(Territory::new("Afghanistan"));
+    let asia_me = territories.add_node(Territory::new("Middle East"));
+    let asia_se = territories.add_node(Territory::new("Southeast Asia"));
+    let asia_ka = territories.add_node(Territory::new("Kamchatka"));
+    let asia_ur = territories.add_node(Territory::new("Ural"));
+
+    territories.add_edge(aus_id, asia_se, Border::sea());
+    territories.add_edge(asia_se, asia_ch, Border::land());
+    territories.add_edge(asia_se, asia_in, Border::land());
+    territories.add_edge(asia_in, asia_af, Border::land());
+    territories.add_edge(asia_in, asia_me, Border::land());
+    territories.add_edge(asia_in, asia_ch, Border::land());
+    territories.add_edge(asia_me, asia_af, Border::land());
+    territories.add_edge(asia_ch, asia_af, Border::land());
+    territories.add_edge(asia_ch, asia_ur, Border::land());
+    territories.add_edge(asia_ch, asia_si, Border::land());
+    territories.add_edge(asia_ch, asia_mo, Border::land());
+    territories.add_edge(asia_af, asia_ur, Border::land());
+    territories.add_edge(asia_ja, asia_mo, Border::sea());
+    territories.add_edge(asia_ja, asia_ka, Border::sea());
+    territories.add_edge(asia_mo, asia_si, Border::land());
+    territories.add_edge(asia_mo, asia_ir, Border::land());
+    territories.add_edge(asia_mo, asia_ka, Border::land());
+    territories.add_edge(asia_si, asia_ya, Border::land());
+    territories.add_edge(asia_si, asia_ur, Border::land());
+    territories.add_edge(asia_si, asia_ir, Border::land());
+    territories.add_edge(asia_ka, asia_ir, Border::land());
+    territories.add_edge(asia_ka, asia_ya, Border::land());
+    territories.add_edge(asia_ya, asia_ir, Border::land());
 
     territories
 }
 
-fn print_all_territories(territories: &UnGraph<&'static str, ()>) {
-    println!("World with {} territories has been set up. Territories:\n", territories.node_count());
+// Describes a territory by name, plus its owner and army count once it has been
+// assigned. Army counts hidden by fog of war are shown as "?".
+fn describe_territory(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    viewer: Option<usize>,
+    fog_of_war: bool,
+    territory_index: u32) -> String {
+    let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap().name;
+    match territory_owner(players, territory_index) {
+        Some(owner_idx) => format!(
+            "{}{} (Owner: {}, Armies: {})",
+            territory_name,
+            capitals::capital_marker(players, territory_index),
+            players[owner_idx].name,
+            visible_army_count(territories, players, viewer, fog_of_war, owner_idx, territory_index)),
+        None => territory_name.to_string(),
+    }
+}
 
-    for (node_index, weight) in territories.node_references() {
-        println!("Territory: {}", weight);
+// Continents group territories of the map by name, and give a bonus to the
+// player holding all of their territories.
+fn setup_continents(territories: &UnGraph<Territory, Border>) -> Vec<Continent> {
+    let continent = |name: &str, bonus: u32, territory_names: &[&str]| {
+        let territory_indices = territory_names
+            .iter()
+            .map(|territory_name| {
+                territories
+                    .node_references()
+                    .find(|(_, territory)| territory.name == *territory_name)
+                    .map(|(node_index, _)| node_index.index() as u32)
+                    .unwrap_or_else(|| panic!("Unknown territory {} in continent {}", territory_name, name))
+            })
+            .collect();
+        Continent { name: name.to_string(), bonus, territories: territory_indices }
+    };
+
+    vec![
+        continent("Australia", 2, &[
+            "Western Australia", "Eastern Australia", "New Guinea", "Indonesia",
+        ]),
+        continent("Asia", 7, &[
+            "India", "China", "Siberia", "Mongolia", "Japan", "Yakutsk",
+            "Irkutsk", "Afghanistan", "Middle East", "Southeast Asia", "Kamchatka", "Ural",
+        ]),
+    ]
+}
 
-        for neighbor in territories.neighbors(node_index) {
-            let neighbor_weight = territories.node_weight(neighbor).unwrap();
-            println!("  Neighbor: {}", neighbor_weight);
+// Territories that can be reached from the given one by crossing a single
+// border, with the border crossed, in index order.
+fn crossable_borders(territories: &UnGraph<Territory, Border>, territory_index: u32) -> Vec<(u32, Border)> {
+    let mut borders: Vec<(u32, Border)> = territories
+        .edges(petgraph::graph::NodeIndex::new(territory_index as usize))
+        .filter(|edge| edge.weight().can_cross_from(territory_index))
+        .map(|edge| {
+            let other_end = if edge.source().index() as u32 == territory_index { edge.target() } else { edge.source() };
+            (other_end.index() as u32, *edge.weight())
+        })
+        .collect();
+    borders.sort_by_key(|(neighbor_index, _)| *neighbor_index);
+    borders
+}
+
+fn print_all_territories(
+    territories: &UnGraph<Territory, Border>,
+    players: &[Player],
+    viewer: Option<usize>,
+    fog_of_war: bool) {
+    println!("World with {} territories. Territories:\n", territories.node_count());
+
+    for (node_index, territory) in territories.node_references() {
+        let mut features = territory.features();
+        if let Some(owner_idx) = territory_owner(players, node_index.index() as u32) {
+            let buildings = players[owner_idx].buildings.get(&(node_index.index() as u32)).into_iter().flatten();
+            features.extend(buildings.map(|building| building.name().to_string()));
+        }
+        println!("Territory: {}{}",
+            describe_territory(territories, players, viewer, fog_of_war, node_index.index() as u32),
+            if features.is_empty() { String::new() } else { format!(" [{}]", features.join(", ")) });
+
+        for (neighbor_index, border) in crossable_borders(territories, node_index.index() as u32) {
+            let by_sea = if border.kind == BorderKind::Sea { " (by sea)" } else { "" };
+            let one_way = if border.one_way_from.is_some() { " (one way)" } else { "" };
+            println!("  Neighbor: {}{}{}",
+                describe_territory(territories, players, viewer, fog_of_war, neighbor_index),
+                by_sea,
+                one_way);
         }
-        println!("");
+        println!();
     }
 }
 
 fn add_armies_to_player(
-    player: &mut Player,) {
-    let total_territories: u32 = player.army_per_territory.len() as u32;
-    let additional_armies = std::cmp::max(3, total_territories / 3);
+    territories: &UnGraph<Territory, Border>,
+    player: &mut Player,
+    rules: &RuleSet,
+    bought_armies: u32) {
+    let held_territories: Vec<Territory> = player.army_per_territory
+        .keys()
+        .map(|territory_index| territories[petgraph::graph::NodeIndex::new(*territory_index as usize)].clone())
+        .collect();
+    let n_cities = economy::count_cities(player) + held_territories.iter().filter(|territory| territory.city).count() as u32;
+    let additional_armies = rules.reinforcements_for(&held_territories)
+        + n_cities * rules.city_reinforcements
+        + bought_armies;
 
     println!(
         "Player {} receives {} additional armies to deploy.",
         player.name, additional_armies);
 
+    // Territories are visited in index order, so that seeded games always
+    // place reinforcements the same way.
+    let mut sorted_territory_indices: Vec<u32> = player.army_per_territory.keys().cloned().collect();
+    sorted_territory_indices.sort();
+
     let mut additional_armies_count = 0;
     'outer_loop: loop {
-        for (_territory_index, armies) in player.army_per_territory.iter_mut() {
+        for territory_index in sorted_territory_indices.iter() {
             // We need to check if we've already assigned enough armies since
             // we iterate over all territories
             if additional_armies_count >= additional_armies {
                 break 'outer_loop;
             }
 
-            *armies += 1;
+            *player.army_per_territory.get_mut(territory_index).unwrap() += 1;
             additional_armies_count += 1;
         }
     }
 }
 
-fn perform_attack(
-    territories: &UnGraph<&'static str, ()>,
-    players: &mut Vec<Player>,
-    attacker_idx: usize,
-    defender_idx: usize,
-    attacking_territory_index: u32,
-    target_territory_index: u32,) -> bool {
-
-    // We currently hard-code to using the maximum number of armies rather
-    // than asking every time.
-    let use_max_armies = true;
-
-    let attacking_territory_name = territories.node_weight(petgraph::graph::NodeIndex::new(attacking_territory_index as usize)).unwrap();
-    let target_territory_name = territories.node_weight(petgraph::graph::NodeIndex::new(target_territory_index as usize)).unwrap();
-
-    println!(
-        "Player {} is attacking from {} to {}",
-        players[attacker_idx].name, attacking_territory_name, target_territory_name);
-
-    let n_attack_armies = *players[attacker_idx].army_per_territory.get(&attacking_territory_index).unwrap();
-    println!("Player {} has {} armies in {}",
-        players[attacker_idx].name,
-        n_attack_armies,
-        attacking_territory_name);
-    let max_attack_armies = std::cmp::min(n_attack_armies - 1, 3);
-
-    let mut n_attacking_armies;
-    if use_max_armies {
-        n_attacking_armies = max_attack_armies;
-        println!("Player {} is attacking with {} armies", players[attacker_idx].name, n_attacking_armies);
-    }
-    else {
-        if max_attack_armies == 1 {
-            println!("Player {} must attack with 1 army from {}, hence proceeding with 1 army.",
-                players[attacker_idx].name,
-                attacking_territory_name);
-            n_attacking_armies = 1;
-        }
-        else {
-            print!("Choose number of armies to attack with (between 1 and {}): ", max_attack_armies);
-
-            // Need to flush stdout to ensure the prompt appears before reading input
-            io::stdout().flush().expect("Failed to flush stdout");
-
-            let mut n_attacking_armies_input = String::new();
-            io::stdin()
-                .read_line(&mut n_attacking_armies_input)
-                .expect("Failed to read line");
-            n_attacking_armies = n_attacking_armies_input.trim().parse().expect("Please type a number!");
-            if n_attacking_armies > max_attack_armies {
-                n_attacking_armies = max_attack_armies;
-                println!("Requested too many attacking armies, reducing to {}", n_attacking_armies);
-            }
-            if n_attacking_armies == 0 {
-                n_attacking_armies = 1;
-                println!("Cannot attack with zero armies, increasing to 1.");
-            }
+// Returns the index of the winner, or of one player of the winning team, once
+// the game is over.
+fn check_game_over(
+    players: &mut [Player],
+    territories: &UnGraph<Territory, Border>,
+    continents: &[Continent],
+    rules: &RuleSet,
+    attacker_idx: usize) -> Option<usize> {
+    if rules.game_mode == GameMode::Capitals {
+        if let Some(winner_idx) = capitals::check_capitals(players, rules) {
+            println!("Game Over! {} holds enough capitals to win.", teams::describe_side(players, winner_idx));
+            return Some(winner_idx);
         }
     }
 
-    let n_defend_armies = *players[defender_idx].army_per_territory.get(&target_territory_index).unwrap();
-    println!("Player {} has {} armies in {}",
-        players[defender_idx].name,
-        n_defend_armies,
-        target_territory_name);
-    let max_defend_armies = std::cmp::min(n_defend_armies,2);
+    if let Some(winner_idx) = missions::check_missions(players, continents, attacker_idx) {
+        let mission = players[winner_idx].mission.as_ref().unwrap();
+        println!("Game Over! Player {} has completed their secret mission: {}.",
+            players[winner_idx].name,
+            missions::describe_mission(mission, continents));
+        return Some(winner_idx);
+    }
 
-    let mut n_defending_armies;
-    if use_max_armies {
-        n_defending_armies = max_defend_armies;
-        println!("Player {} is defending with {} armies", players[defender_idx].name, n_defending_armies);
-    }
-    else {
-        print!("Choose number of armies to defend with (between 1 and {}): ", max_defend_armies);
-
-        io::stdout().flush().expect("Failed to flush stdout");
-
-        let mut n_defending_armies_input = String::new();
-        io::stdin()
-            .read_line(&mut n_defending_armies_input)
-            .expect("Failed to read line");
-        n_defending_armies = n_defending_armies_input.trim().parse().expect("Please type a number!");
-        if n_defending_armies > max_defend_armies {
-            n_defending_armies = max_defend_armies;
-            println!("Requested too many defending armies, reducing to {}", n_defending_armies);
-        }
-        if n_defending_armies == 0 {
-            n_defending_armies = 1;
-            println!("Cannot defend with zero armies, increasing to 1.");
+    // Teammates win together, so territories held by a whole team count.
+    let total_territories = territories.node_count();
+    for player_idx in 0..players.len() {
+        let n_territories: usize = (0..players.len())
+            .filter(|other_idx| teams::are_allies(players, player_idx, *other_idx))
+            .map(|other_idx| players[other_idx].army_per_territory.len())
+            .sum();
+        if n_territories == total_territories {
+            println!("Game Over! {} has conquered all territories.", teams::describe_side(players, player_idx));
+            return Some(player_idx);
         }
     }
 
-    let mut rng = rand::thread_rng();
-
-    let mut attacking_dice_rolls = Vec::<u8>::new(); // Placeholder for dice rolls
-    for _ in 0..n_attacking_armies {
-        let dice_roll = rng.gen_range(1..=6);
-        println!("Attacker rolled: {}", dice_roll);
-        attacking_dice_rolls.push(dice_roll);
-    }
-    let mut defending_dice_rolls = Vec::<u8>::new(); // Placeholder for dice rolls
-    for _ in 0..n_defending_armies {
-        let dice_roll = rng.gen_range(1..=6);
-        println!("Defender rolled: {}", dice_roll);
-        defending_dice_rolls.push(dice_roll);
+    // The neutral player does not need to be eliminated, so the game is also
+    // over once only one side of the other players has territories left.
+    let acting_player_indices: Vec<usize> = (0..players.len())
+        .filter(|player_idx| !players[*player_idx].is_neutral)
+        .collect();
+    let remaining_player_indices: Vec<usize> = acting_player_indices
+        .iter()
+        .cloned()
+        .filter(|player_idx| !players[*player_idx].army_per_territory.is_empty())
+        .collect();
+    let is_one_side = |player_indices: &[usize]| {
+        player_indices
+            .iter()
+            .all(|player_idx| teams::are_allies(players, player_indices[0], *player_idx))
+    };
+    if !remaining_player_indices.is_empty()
+        && is_one_side(&remaining_player_indices)
+        && !is_one_side(&acting_player_indices) {
+        println!("Game Over! {} has eliminated all other players.",
+            teams::describe_side(players, remaining_player_indices[0]));
+        return Some(remaining_player_indices[0]);
     }
 
-    attacking_dice_rolls.sort_by(|a, b| b.cmp(a)); // Sort descending
-    defending_dice_rolls.sort_by(|a, b| b.cmp(a)); // Sort descending
+    None
+}
 
-    let n_comparisons = std::cmp::min(attacking_dice_rolls.len(), defending_dice_rolls.len());
-    for i in 0..n_comparisons {
-        if attacking_dice_rolls[i] > defending_dice_rolls[i] {
-            println!("Attacker wins comparison {}: {} vs {}", i + 1, attacking_dice_rolls[i], defending_dice_rolls[i]);
-            // Defender loses one army
-            let defender_armies = players[defender_idx].army_per_territory.get_mut(&target_territory_index).unwrap();
-            *defender_armies -= 1;
-        } else {
-            println!("Defender wins comparison {}: {} vs {}", i + 1, defending_dice_rolls[i], attacking_dice_rolls[i]);
-            // Attacker loses one army
-            let attacker_armies = players[attacker_idx].army_per_territory.get_mut(&attacking_territory_index).unwrap();
-            *attacker_armies -= 1;
+// Announces the winning side when the game is decided on score rather than by
+// conquest, and returns the index of its best-placed player.
+fn print_score_winner(players: &[Player], continents: &[Continent], rules: &RuleSet) -> usize {
+    let (winner_idx, score) = scoring::winning_side(players, continents, rules);
+    println!("{} wins with a score of {}.", teams::describe_side(players, winner_idx), score);
+    winner_idx
+}
+
+// Reveals every player's secret mission once the game is over.
+fn print_missions(players: &[Player], continents: &[Continent]) {
+    for player in players.iter() {
+        if let Some(mission) = &player.mission {
+            println!("Player {}'s secret mission was: {}.", player.name, missions::describe_mission(mission, continents));
         }
     }
+}
 
-    let new_n_attack_armies = *players[attacker_idx].army_per_territory.get(&attacking_territory_index).unwrap();
-    println!("Player {} now has {} armies in {}",
-        players[attacker_idx].name,
-        new_n_attack_armies,
-        attacking_territory_name);
-
-    let new_n_defend_armies = *players[defender_idx].army_per_territory.get(&target_territory_index).unwrap();
-    println!("Player {} now has {} armies in {}",
-        players[defender_idx].name,
-        new_n_defend_armies,
-        target_territory_name);
-
-    if new_n_defend_armies == 0 {
-        players[defender_idx].army_per_territory.remove(&target_territory_index);
-
-        println!("Player {} conquered territory {}!",
-            players[attacker_idx].name,
-            target_territory_name);
-
-        // We move at least the number of attacking armies used in the attack,
-        // up to the maximum number of armies minus one left behind in the
-        // attacking territory.
-        let max_movable_armies = new_n_attack_armies - 1;
-        let min_movable_armies = n_attacking_armies;
-        if min_movable_armies == max_movable_armies {
-            println!("Automatically moving {} armies into conquered territory {}",
-                min_movable_armies,
-                target_territory_name);
-            players[attacker_idx].army_per_territory.insert(target_territory_index, min_movable_armies);
-            let attacker_armies = players[attacker_idx].army_per_territory.get_mut(&attacking_territory_index).unwrap();
-            *attacker_armies -= min_movable_armies;
-            return (new_n_defend_armies == 0) || (new_n_attack_armies == 1);
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("Run with --help to see the available options.");
+            std::process::exit(2);
         }
+    };
+    if options.help {
+        cli::print_help();
+        return;
+    }
+    if options.subcommand == Subcommand::GenerateMap {
+        let map_file = options.export_file.as_deref().unwrap();
+        let generated = map_generator::generate_map(&options.map_settings, &mut seeded_rng(options.seed))
+            .and_then(|map| map.save(map_file));
+        if let Err(error) = generated {
+            eprintln!("Could not generate the map: {}", error);
+            std::process::exit(1);
+        }
+        println!("Map written to {}.", map_file);
+        return;
+    }
 
-        print!("Choose number of armies to move into conquered territory (between {} and {}): ",
-            min_movable_armies,
-            max_movable_armies);
-
-        io::stdout().flush().expect("Failed to flush stdout");
+    if options.subcommand == Subcommand::EditMap {
+        let mut input = open_input(&options);
+        if let Err(error) = map_editor::edit_map(options.export_file.as_deref().unwrap(), &mut input) {
+            eprintln!("Invalid map: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-        let mut n_movable_armies_input = String::new();
-        io::stdin()
-            .read_line(&mut n_movable_armies_input)
-            .expect("Failed to read line");
-        let mut n_movable_armies = n_movable_armies_input.trim().parse().expect("Please type a number!");
-        if n_movable_armies > max_movable_armies {
-            n_movable_armies = max_movable_armies;
-            println!("Requested too many movable armies, reducing to {}", n_movable_armies);
+    // The map is chosen with "--map <default or file>".
+    let mut map = match Map::load(options.map.as_deref().unwrap_or("default")) {
+        Ok(map) => map,
+        Err(error) => {
+            eprintln!("Invalid map: {}", error);
+            std::process::exit(1);
+        }
+    };
+    // A campaign changes the map with everything its earlier games left behind.
+    let mut campaign = options.campaign.as_ref().map(|campaign_file| {
+        match Campaign::load(campaign_file).and_then(|campaign| campaign.apply(&mut map).map(|_| campaign)) {
+            Ok(campaign) => campaign,
+            Err(error) => {
+                eprintln!("Invalid campaign: {}", error);
+                std::process::exit(1);
+            }
         }
-        if n_movable_armies < min_movable_armies {
-            n_movable_armies = min_movable_armies;
-            println!("Requested too few movable armies, increasing to {}", n_movable_armies);
+    });
+    if options.subcommand == Subcommand::CheckMap {
+        if !map_check::print_report(&map.territories, &map.continents) {
+            std::process::exit(1);
         }
-
-        players[attacker_idx].army_per_territory.insert(target_territory_index, n_movable_armies);
-        let attacker_armies = players[attacker_idx].army_per_territory.get_mut(&attacking_territory_index).unwrap();
-        *attacker_armies -= n_movable_armies;
+        return;
     }
-
-    if new_n_attack_armies == 1 {
-        println!("Player {} only has one army left, attack on {} cannot continue",
-            players[attacker_idx].name,
-            target_territory_name);
+    if let Some(export_file) = &options.export_file {
+        if let Err(error) = export::write_board(export_file, &map, &[], false) {
+            eprintln!("Could not export the map: {}", error);
+            std::process::exit(1);
+        }
+        println!("Map written to {}.", export_file);
+        return;
+    }
+    if let Some(problem) = map_check::find_problems(&map.territories, &map.continents).first() {
+        eprintln!("Invalid map: {}", problem);
+        eprintln!("Run check-map to see every problem with the map.");
+        std::process::exit(1);
     }
 
-    (new_n_defend_armies == 0) || (new_n_attack_armies == 1)
-}
+    println!("\n==== Welcome to Hazard, the Risk-like strategy game! ====");
 
-fn check_game_over(players: &Vec<Player>, territories: &UnGraph<&'static str, ()>) -> bool {
-    let total_territories = territories.node_count();
-    for player in players {
-        let n_territories = player.army_per_territory.len();
-        if n_territories == total_territories {
-            println!("Game Over! Player {} has conquered all territories.", player.name);
-            return true;
+    // Rules are chosen with "--rules <preset or file>", where the preset is
+    // "classic" (the default) or "house".
+    let rules = match RuleSet::load(options.rules.as_deref().unwrap_or("classic")) {
+        Ok(rules) => rules,
+        Err(error) => {
+            eprintln!("Invalid rules: {}", error);
+            std::process::exit(1);
+        }
+    };
+    rules.print_summary();
+    println!();
+
+    // Puzzles are a single attack phase from the position in the puzzle file.
+    if let Some(puzzle_file) = &options.puzzle {
+        let puzzle = match Puzzle::load(puzzle_file, &map, &rules) {
+            Ok(puzzle) => puzzle,
+            Err(error) => {
+                eprintln!("Invalid puzzle: {}", error);
+                std::process::exit(1);
+            }
+        };
+        let mut input = open_input(&options);
+        let mut dice: Box<dyn Dice> = match &puzzle.dice {
+            Some(rolls) => Box::new(LoadedDice::new(rolls)),
+            None => Box::new(RandomDice(StdRng::seed_from_u64(seeded_rng(options.seed).gen()))),
+        };
+        if !puzzle::play(&puzzle, &map, &rules, &mut input, dice.as_mut()) {
+            std::process::exit(1);
         }
+        return;
     }
-    false
-}
-
-fn main() {
-    println!("\n==== Welcome to Hazard, the Risk-like strategy game! ====");
 
-    let territories = setup_territories();
-    print_all_territories(&territories);
+    let mission_deck = if rules.game_mode == GameMode::Missions {
+        match MissionDeck::load(rules.missions_file.as_deref()) {
+            Ok(mission_deck) => Some(mission_deck),
+            Err(error) => {
+                eprintln!("Invalid mission deck: {}", error);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let scenario = options.scenario.as_ref().map(|scenario_file| {
+        match Scenario::load(scenario_file, &map, &rules) {
+            Ok(scenario) => scenario,
+            Err(error) => {
+                eprintln!("Invalid scenario: {}", error);
+                std::process::exit(1);
+            }
+        }
+    });
+    if let Some(scenario) = &scenario {
+        scenario.print_summary(&map);
+    }
 
-    print!("Please enter the number of players between 1 and 5: ");
+    let mut input = open_input(&options);
 
-    // Need to flush stdout to ensure the prompt appears before reading input
-    io::stdout().flush().expect("Failed to flush stdout");
+    let mut rng = seeded_rng(options.seed);
+    let mut dice = RandomDice(StdRng::seed_from_u64(rng.gen()));
 
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read line");
+    if let Some(campaign) = &campaign {
+        campaign.print_standings();
+    }
 
-    let number_of_players: i32 = input.trim().parse().expect("Please type a number!");
-    assert!(
-        number_of_players >= 1 && number_of_players <= 5,
-        "Number of players must be between 1 and 5"
-    );
-    println!("==== Setting up game for {} players ====", number_of_players);
+    let (players, winner_idx) = play_game(&map, &rules, mission_deck.as_ref(), scenario.map(|scenario| scenario.players), &options, &mut input, &mut dice, &mut rng);
 
-    let mut player_names = Vec::new();
-    for i in 0..number_of_players {
-        print!("Enter name for Player {}: ", i + 1);
-        io::stdout().flush().expect("Failed to flush stdout");
+    if let (Some(campaign), Some(campaign_file)) = (&mut campaign, &options.campaign) {
+        campaign.record_game(&mut map, &players, winner_idx, &mut input);
+        if let Err(error) = campaign.save(campaign_file) {
+            eprintln!("Could not save the campaign: {}", error);
+            std::process::exit(1);
+        }
+        campaign.print_standings();
+    }
+}
 
-        let mut name_input = String::new();
-        io::stdin()
-            .read_line(&mut name_input)
-            .expect("Failed to read line");
+// With "--script <file>" or "replay <file>" every decision is read from a
+// script of labelled commands rather than typed in at the prompts.
+fn open_input(options: &Options) -> Input {
+    match &options.script {
+        Some(script_path) => match Input::script(script_path) {
+            Ok(input) => input,
+            Err(error) => {
+                eprintln!("Invalid script: {}", error);
+                std::process::exit(1);
+            }
+        },
+        None => Input::stdin(),
+    }
+}
 
-        player_names.push(name_input.trim().to_string());
+fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
     }
-    println!("");
+}
 
-    let mut players = setup_players(player_names);
+// Asks for the players, shares out the territories and starting armies, and
+// deals missions or chooses capitals if the game mode needs them.
+fn set_up_game(
+    map: &Map,
+    rules: &RuleSet,
+    mission_deck: Option<&MissionDeck>,
+    options: &Options,
+    input: &mut Input,
+    rng: &mut StdRng) -> Vec<Player> {
+    let territories = &map.territories;
+    let players_and_colors = match &options.players {
+        Some(players_and_colors) => players_and_colors.clone(),
+        None => prompt_players(input),
+    };
+    println!("==== Setting up game for {} players ====", players_and_colors.len());
+
+    let mut players = setup_players(players_and_colors);
+    add_neutral_player(&mut players, rules);
 
     // Assign territories and initial armies here
-    assign_territories_and_armies_to_players(&territories, &mut players);
+    teams::assign_teams(&mut players, input);
+
+    assign_territories_and_armies_to_players(
+        territories,
+        &mut players,
+        rules,
+        options.setup_mode,
+        options.placement,
+        rng,
+        input);
+
+    if let Some(mission_deck) = mission_deck {
+        missions::deal_missions(mission_deck, &mut players, territories, &map.continents, rng);
+    }
+
+    if rules.game_mode == GameMode::Capitals {
+        println!("\n==== Each player now chooses a capital ====");
+        capitals::choose_capitals(territories, &mut players, input);
+    }
+    players
+}
+
+// Plays a whole game from setup, or from the players of a scenario, to the final
+// standings. Returns the players as they were at the end of the game, and the
+// index of the winner.
+#[allow(clippy::too_many_arguments)]
+fn play_game(
+    map: &Map,
+    rules: &RuleSet,
+    mission_deck: Option<&MissionDeck>,
+    scenario_players: Option<Vec<Player>>,
+    options: &Options,
+    input: &mut Input,
+    dice: &mut dyn Dice,
+    rng: &mut StdRng) -> (Vec<Player>, usize) {
+    let territories = &map.territories;
+    let continents = &map.continents;
+    println!("World has been set up.");
+    print_all_territories(territories, &[], None, false);
+
+    // A scenario sets up the players and the board itself.
+    let mut players = match scenario_players {
+        Some(players) => players,
+        None => set_up_game(map, rules, mission_deck, options, input, rng),
+    };
 
     // Now we start the game
-    'game_loop: loop {
+    let start_time = Instant::now();
+    let mut round = 1;
+    let mut diplomacy = Diplomacy::new();
+    let winner_idx = 'game_loop: loop {
+        diplomacy.remove_expired_pacts(&players, round);
+
         for player_idx in 0..players.len() {
+            // Neutral and eliminated players do not take turns.
+            if players[player_idx].is_neutral || players[player_idx].army_per_territory.is_empty() {
+                continue;
+            }
+
+            if let Some(time_limit_minutes) = rules.time_limit_minutes {
+                if start_time.elapsed() >= Duration::from_secs(60 * time_limit_minutes as u64) {
+                    println!("\nGame Over! The time limit of {} minutes has been reached.", time_limit_minutes);
+                    break 'game_loop print_score_winner(&players, continents, rules);
+                }
+            }
 
             {
                 let mut_player = &mut players[player_idx];
                 println!("\n==== Player {}'s turn ====", mut_player.name);
-                println!("\n==== Reinforcement phase ====");
+            }
 
-                add_armies_to_player(mut_player);
-                println!();
+            print_all_territories(territories, &players, Some(player_idx), rules.fog_of_war);
+
+            if let Some(mission) = &players[player_idx].mission {
+                println!("Your secret mission: {}.", missions::describe_mission(mission, continents));
             }
 
+            diplomacy.remove_proposals_of_eliminated(&players);
+            diplomacy.print_status(territories, &players);
+            if rules.diplomacy {
+                diplomacy.negotiate(territories, &mut players, player_idx, round, input);
+                // Territory swaps can complete a mission or hand over a capital.
+                if let Some(winner_idx) = check_game_over(&mut players, territories, continents, rules, player_idx) {
+                    break 'game_loop winner_idx;
+                }
+            }
+
+            println!("\n==== Reinforcement phase ====");
+            // Purchases come before placement, so bought armies are placed
+            // with the reinforcements.
+            let mut bought_armies = 0;
+            if rules.economy {
+                economy::earn_gold(&mut players, player_idx, continents, rules);
+                bought_armies = economy::purchase(territories, &mut players, player_idx, rules, input);
+            }
+            add_armies_to_player(territories, &mut players[player_idx], rules, bought_armies);
+            println!();
+
             let mut attack_count = 0;
 
             let mut defender_idx_option: Option<usize> = None;
@@ -603,26 +986,23 @@ fn main() {
 
             let mut attack_finished = false;
 
+            // Choices made this turn without rolling dice can be taken back.
+            let mut history = History::new();
+
             println!("==== Attack phase ====");
             loop {
 
                 {
                     println!("==== Attack phase round {} ====", attack_count + 1);
 
+                    print_player(territories, &players, player_idx, Some(player_idx), rules.fog_of_war);
                     let player = &players[player_idx];
-                    print_player(&territories, player);
 
                     let mut choose_new_attack = true;
 
                     if attack_count > 0 && !attack_finished {
-                        print!("Do you want to attack the territory again? (y/n): ");
-                        io::stdout().flush().expect("Failed to flush stdout");
-
-                        let mut repeat_attack = String::new();
-                        io::stdin()
-                            .read_line(&mut repeat_attack)
-                            .expect("Failed to read line");
-                        match repeat_attack.trim() {
+                        let repeat_attack = input.prompt_flag("attack", "blitz", "Do you want to attack the territory again? (y/n): ");
+                        match repeat_attack.as_str() {
                             "y" | "Y" => {
                                 choose_new_attack = false;
                             }
@@ -637,14 +1017,29 @@ fn main() {
                     }
 
                     if choose_new_attack {
-                        print!("Do you want to attack any territory? (y/n): ");
-                        io::stdout().flush().expect("Failed to flush stdout");
-
-                        let mut input = String::new();
-                        io::stdin()
-                            .read_line(&mut input)
-                            .expect("Failed to read line");
-                        match input.trim() {
+                        let attack_any = input.prompt_yes_no("attack", "Do you want to attack any territory? (y/n, or undo): ");
+                        match attack_any.as_str() {
+                            "undo" => {
+                                // Only the armies moved into a conquered territory can
+                                // be taken back, and are then moved again.
+                                match history.undo(&mut players) {
+                                    Some(Action::ConquestMove { from, to, min_armies }) => {
+                                        println!("Conquest move undone.");
+                                        combat::move_into_conquered_territory(
+                                            territories,
+                                            &mut players,
+                                            player_idx,
+                                            from,
+                                            to,
+                                            min_armies,
+                                            rules,
+                                            &mut history,
+                                            input);
+                                    }
+                                    _ => input.reject("There is nothing to undo."),
+                                }
+                                continue;
+                            }
                             "y" | "Y" => {
                                 // Get sorted list of territory indices, since it's easier for the player
                                 // to read when it is ordered.
@@ -657,79 +1052,73 @@ fn main() {
                                 for territory_index in sorted_attacking_territory_indices {
                                     println!("Territory index: {}, territory name: {}",
                                         territory_index,
-                                        territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap());
+                                        territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap().name);
                                 }
 
-                                print!("Attacking from territory index: ");
-                                io::stdout().flush().expect("Failed to flush stdout");
-
-                                let mut selected_index = String::new();
-                                io::stdin()
-                                    .read_line(&mut selected_index)
-                                    .expect("Failed to read line");
-                                attacking_territory_index = selected_index.trim().parse().expect("Please type a number!");
+                                attacking_territory_index = input.prompt_number("attack", "Attacking from territory index: ");
 
                                 if let Some(armies) = player.army_per_territory.get(&attacking_territory_index) {
-                                    if *armies < 2 {
-                                        println!("Not enough armies to attack from this territory.");
+                                    if *armies <= rules.armies_left_behind {
+                                        input.reject("Not enough armies to attack from this territory.");
                                         continue;
                                     }
                                 } else {
-                                    println!("You do not own this territory.");
+                                    input.reject("You do not own this territory.");
                                     continue;
                                 }
 
                                 let mut sorted_target_territory_indices = Vec::new();
-                                sorted_target_territory_indices.sort();
-                                for neighbor in territories.neighbors(petgraph::graph::NodeIndex::new(attacking_territory_index as usize)) {
-                                    if let Some(_) = player.army_per_territory.get(&neighbor.index().try_into().unwrap()) {
-                                        // Skip territories owned by the player
-                                        continue;
+                                for (neighbor_index, _) in crossable_borders(territories, attacking_territory_index) {
+                                    match territory_owner(&players, neighbor_index) {
+                                        Some(owner_idx) if !teams::are_allies(&players, player_idx, owner_idx)
+                                            && !diplomacy.has_pact(player_idx, owner_idx, round) => {
+                                            sorted_target_territory_indices.push(neighbor_index);
+                                        }
+                                        // Skip territories owned by the player, their teammates, or
+                                        // players they have a pact with
+                                        _ => continue,
                                     }
-                                    sorted_target_territory_indices.push(neighbor.index());
                                 }
 
                                 if sorted_target_territory_indices.is_empty() {
-                                    println!("No target territories available to attack from {}!",
-                                      territories.node_weight(petgraph::graph::NodeIndex::new(attacking_territory_index as usize)).unwrap());
+                                    input.reject(&format!("No target territories available to attack from {}!",
+                                      territories.node_weight(petgraph::graph::NodeIndex::new(attacking_territory_index as usize)).unwrap().name));
                                     continue;
                                 }
 
                                 sorted_target_territory_indices.sort();
 
                                 println!("\nSelect target territory index:");
-                                for territory_index in sorted_target_territory_indices {
+                                for territory_index in sorted_target_territory_indices.iter() {
                                     println!("Territory index: {}, territory name: {}",
                                         territory_index,
-                                        territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap());
+                                        territories.node_weight(petgraph::graph::NodeIndex::new(*territory_index as usize)).unwrap().name);
                                 }
 
-                                print!("Targeting territory index: ");
-                                io::stdout().flush().expect("Failed to flush stdout");
-
-                                let mut target_index = String::new();
-                                io::stdin()
-                                    .read_line(&mut target_index)
-                                    .expect("Failed to read line");
-                                target_territory_index = target_index.trim().parse().expect("Please type a number!");
-
-                                // defender is the player who owns the target territory
-                                for other_player in players.iter() {
-                                    if other_player.name != player.name {
-                                        if other_player.army_per_territory.contains_key(&target_territory_index) {
-                                            defender_idx_option = Some(players.iter().position(|p| p.name == other_player.name).unwrap() as usize);
-                                            break;
-                                        }
-                                    }
+                                target_territory_index = input.prompt_number("attack", "Targeting territory index: ");
+                                if !sorted_target_territory_indices.contains(&target_territory_index) {
+                                    input.reject(&format!("You cannot attack this territory from {}.",
+                                        territories.node_weight(petgraph::graph::NodeIndex::new(attacking_territory_index as usize)).unwrap().name));
+                                    continue;
                                 }
+
+                                // defender is the player who owns the target territory, which
+                                // is never the player or one of their teammates
+                                defender_idx_option = territory_owner(&players, target_territory_index);
                             }
                             "n" | "N" => {
-                                println!("==== Attack phase has ended, player {}'s turn is over ====", player.name);
+                                println!("==== Attack phase has ended for player {} ====", player.name);
                                 break;
                             }
+                            // Undoing a conquest move asks for the move again straight
+                            // away, so there is never anything left to redo.
+                            "redo" => {
+                                input.reject("There is nothing to redo.");
+                                continue;
+                            }
                             _ => {
-                                println!("Invalid input, skipping attack phase.");
-                                break;
+                                input.reject("Please answer y or n, or undo.");
+                                continue;
                             }
                         }
                     }
@@ -741,24 +1130,246 @@ fn main() {
                     // if the attacker only has one army left on the attacking territory
                     attack_finished =
                         perform_attack(
-                            &territories,
+                            territories,
                             &mut players,
                             player_idx,
                             defender_idx,
                             attacking_territory_index,
-                            target_territory_index);
+                            target_territory_index,
+                            rules,
+                            &mut history,
+                            input,
+                            dice);
 
                     attack_count += 1;
 
                     // Check if one player now has all the territories. If so, we can exit
                     // the game.
-                    if check_game_over(&players, &territories) {
-                        break 'game_loop;
+                    if let Some(winner_idx) = check_game_over(&mut players, territories, continents, rules, player_idx) {
+                        break 'game_loop winner_idx;
                     }
                 }
 
                 println!();
             }
+
+            fortify::fortify(territories, &mut players, player_idx, rules, &mut history, input);
+            println!("==== Player {}'s turn is over ====", players[player_idx].name);
+            write_snapshot(options, map, &players, rules);
+        }
+
+        if let Some(max_turns) = rules.max_turns {
+            if round >= max_turns {
+                println!("\nGame Over! The limit of {} turns has been reached.", max_turns);
+                break 'game_loop print_score_winner(&players, continents, rules);
+            }
+        }
+        round += 1;
+    };
+
+    write_snapshot(options, map, &players, rules);
+    print_missions(&players, continents);
+    scoring::print_standings(&players, continents, rules);
+
+    (players, winner_idx)
+}
+
+// Rewrites the "--snapshot" file with the board as it is now. With fog of war
+// the snapshot only shows who owns what.
+fn write_snapshot(options: &Options, map: &Map, players: &[Player], rules: &RuleSet) {
+    if let Some(snapshot) = &options.snapshot {
+        if let Err(error) = export::write_board(snapshot, map, players, !rules.fog_of_war) {
+            println!("Could not write the board snapshot: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two players draft the map, Ann claiming the even territories and Bo the
+    // odd ones, and each stacks their remaining armies on their first territory.
+    fn draft_lines() -> Vec<String> {
+        let mut lines: Vec<String> = ["2", "Ann", "", "Bo", "", "2"].iter().map(|line| line.to_string()).collect();
+        lines.extend((0..16).map(|territory_index| territory_index.to_string()));
+        lines.push("0 100".to_string());
+        lines.push("1 100".to_string());
+        lines
+    }
+
+    fn scripted_input(lines: &[String]) -> Input {
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+        Input::from_lines(&lines)
+    }
+
+    #[test]
+    fn scripted_game_ends_at_the_turn_limit() {
+        let mut rules = RuleSet::classic();
+        rules.neutral_in_two_player_games = false;
+        rules.max_turns = Some(1);
+
+        let mut lines = draft_lines();
+        // Ann conquers Indonesia (3) from Western Australia (0) and moves in 3
+        // armies, then Bo passes.
+        lines.extend(["y", "0", "3", "3", "n", "n", "n", "n"].iter().map(|line| line.to_string()));
+        let mut input = scripted_input(&lines);
+        let mut dice = LoadedDice::new(&[6, 6, 6, 1]);
+
+        let (players, _) = play_game(&Map::default_map(), &rules, None, None, &Options::default(), &mut input, &mut dice, &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(players[0].army_per_territory.len(), 9);
+        assert_eq!(players[0].army_per_territory[&3], 3);
+        assert_eq!(players[0].total_armies(), 40 + 3);
+        assert_eq!(players[1].army_per_territory.len(), 7);
+        assert_eq!(players[1].total_armies(), 40 - 1 + 3);
+    }
+
+    #[test]
+    fn scenario_games_skip_setup() {
+        let map = Map::default_map();
+        let mut contents = String::from("player Bo\nplayer Ann\nfirst Ann\n");
+        for node_index in map.territories.node_indices() {
+            let (owner, armies) = if node_index.index() == 3 { ("Bo", 1) } else { ("Ann", 10) };
+            contents.push_str(&format!("hold {}, {}, {}\n", map.territories[node_index].name, owner, armies));
         }
+        let scenario = Scenario::parse(&contents, &map, &RuleSet::classic()).unwrap();
+
+        // Ann moves first and wins by taking Indonesia (3) from Western
+        // Australia (0), without any setup prompts.
+        let mut input = Input::from_lines(&["y", "0", "3", "3"]);
+        let mut dice = LoadedDice::new(&[6, 6, 6, 1, 1]);
+        let (players, winner_idx) = play_game(&map, &RuleSet::classic(), None, Some(scenario.players), &Options::default(), &mut input, &mut dice, &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(players[winner_idx].name, "Ann");
+        assert_eq!(players[winner_idx].army_per_territory.len(), 16);
+    }
+
+    #[test]
+    fn scripted_game_ends_when_a_capital_is_taken() {
+        let mut rules = RuleSet::classic();
+        rules.neutral_in_two_player_games = false;
+        rules.game_mode = GameMode::Capitals;
+
+        let mut lines = draft_lines();
+        // Ann's capital is Western Australia (0) and Bo's is Indonesia (3), so
+        // taking Indonesia wins the game straight away.
+        lines.extend(["0", "3", "y", "0", "3", "3"].iter().map(|line| line.to_string()));
+        let mut input = scripted_input(&lines);
+        let mut dice = LoadedDice::new(&[6, 6, 6, 1]);
+
+        let (players, _) = play_game(&Map::default_map(), &rules, None, None, &Options::default(), &mut input, &mut dice, &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(capitals::check_capitals(&players, &rules), Some(0));
+        assert_eq!(players[0].army_per_territory[&3], 3);
+    }
+
+    #[test]
+    fn script_plays_a_game_with_labelled_commands() {
+        let mut rules = RuleSet::classic();
+        rules.neutral_in_two_player_games = false;
+        rules.max_turns = Some(1);
+
+        let mut lines = vec!["players 2", "name Ann", "color red", "name Bo", "color blue", "setup 2"];
+        let claims: Vec<String> = (0..16).map(|territory_index| format!("claim {}", territory_index)).collect();
+        lines.extend(claims.iter().map(|line| line.as_str()));
+        lines.extend([
+            "place 0 100",
+            "place 1 100",
+            // Ann blitzes Indonesia (3) from Western Australia (0), and changes
+            // their mind about how many armies to move in.
+            "attack 0 3 blitz",
+            "move 5",
+            "undo",
+            "move 3",
+            "end",
+            // Ann takes back a fortify move, then makes it again.
+            "fortify 3 2 2",
+            "undo",
+            "redo",
+            "end",
+            // Bo passes.
+            "end",
+            "end",
+        ]);
+        let mut input = Input::script_from_lines(&lines);
+        let mut dice = LoadedDice::new(&[6, 6, 6, 1]);
+
+        let (players, _) = play_game(&Map::default_map(), &rules, None, None, &Options::default(), &mut input, &mut dice, &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(players[0].army_per_territory[&3], 1);
+        assert_eq!(players[0].army_per_territory.len(), 9);
+        assert_eq!(players[0].total_armies(), 40 + 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "There is nothing to redo.")]
+    fn script_fails_on_redo_in_the_attack_phase() {
+        let mut rules = RuleSet::classic();
+        rules.neutral_in_two_player_games = false;
+
+        let mut lines = vec!["players 2", "name Ann", "color red", "name Bo", "color blue", "setup 2"];
+        let claims: Vec<String> = (0..16).map(|territory_index| format!("claim {}", territory_index)).collect();
+        lines.extend(claims.iter().map(|line| line.as_str()));
+        lines.extend(["place 0 100", "place 1 100", "redo"]);
+        let mut input = Input::script_from_lines(&lines);
+
+        play_game(&Map::default_map(), &rules, None, None, &Options::default(), &mut input, &mut LoadedDice::new(&[6]), &mut StdRng::seed_from_u64(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "You cannot attack this territory from Western Australia.")]
+    fn script_fails_on_illegal_attacks() {
+        let mut rules = RuleSet::classic();
+        rules.neutral_in_two_player_games = false;
+
+        let mut lines = vec!["players 2", "name Ann", "color red", "name Bo", "color blue", "setup 2"];
+        let claims: Vec<String> = (0..16).map(|territory_index| format!("claim {}", territory_index)).collect();
+        lines.extend(claims.iter().map(|line| line.as_str()));
+        // New Guinea (2) is Ann's own territory.
+        lines.extend(["place 0 100", "place 1 100", "attack 0 2"]);
+        let mut input = Input::script_from_lines(&lines);
+
+        play_game(&Map::default_map(), &rules, None, None, &Options::default(), &mut input, &mut LoadedDice::new(&[6]), &mut StdRng::seed_from_u64(0));
+    }
+
+    #[test]
+    fn command_line_options_skip_setup_prompts_and_seeds_make_games_reproducible() {
+        let mut rules = RuleSet::classic();
+        rules.neutral_in_two_player_games = false;
+        rules.max_turns = Some(1);
+        let options = Options {
+            players: Some(vec![("Ann".to_string(), None), ("Bo".to_string(), None)]),
+            setup_mode: Some(setup::SetupMode::Random),
+            placement: Some(setup::Placement::Automatic),
+            ..Options::default()
+        };
+
+        // Only the attack and fortify prompts of each turn are left.
+        let play = || {
+            let mut input = Input::from_lines(&["n", "n", "n", "n"]);
+            play_game(&Map::default_map(), &rules, None, None, &options, &mut input, &mut LoadedDice::new(&[1]), &mut StdRng::seed_from_u64(3))
+        };
+        let (first_game, _) = play();
+        let (second_game, _) = play();
+
+        for player_idx in 0..2 {
+            assert_eq!(first_game[player_idx].army_per_territory, second_game[player_idx].army_per_territory);
+            assert_eq!(first_game[player_idx].total_armies(), 40 + 3);
+        }
+    }
+
+    #[test]
+    fn players_choose_different_colours() {
+        // Bo cannot take Ann's colour or the neutral colour, and Cy takes the
+        // first free colour.
+        let mut input = Input::from_lines(&["3", "Ann", "blue", "Bo", "Blue", "grey", "purple", "pink", "Cy", ""]);
+        let players = setup_players(prompt_players(&mut input));
+        let colors: Vec<Color> = players.iter().map(|player| player.color.clone()).collect();
+        assert_eq!(colors, vec![Color::Blue, Color::Pink, Color::Red]);
+
+        // Colours chosen by later players are not handed out to earlier ones.
+        let players = setup_players(vec![("Ann".to_string(), None), ("Bo".to_string(), Some(Color::Red))]);
+        assert_eq!(players[0].color, Color::Blue);
     }
 }
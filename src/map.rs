@@ -0,0 +1,346 @@
+// Maps, either the built-in default map or one loaded from a map file. A map
+// file lists each continent with its bonus, followed by its territories, and
+// then the borders between territories:
+//
+//     continent Australia 2
+//     territory Western Australia
+//     territory Eastern Australia
+//     territory Indonesia
+//     border Western Australia, Eastern Australia
+//     sea Western Australia, Indonesia
+//
+// Borders are over land with "border" and across the sea with "sea", and are
+// one-way routes when written as "border <from> -> <to>".
+// Territories listed before the first continent belong to no continent, which
+// check-map reports. A territory can be given a position for drawing the map,
+// as in "territory Western Australia @ 120 340", and features after a colon, as
+// in "territory Afghanistan @ 300 80: mountains, fortress, production 2". A
+// campaign can also leave a city or a bunker on a territory.
+// Territories are plains of production 1 without a fortress unless stated
+// otherwise. Blank lines and lines starting with '#' are ignored.
+
+use std::fs;
+
+use petgraph::graph::{NodeIndex, UnGraph};
+use petgraph::visit::EdgeRef;
+
+use crate::{setup_continents, setup_territories, Border, BorderKind, Continent, Terrain, Territory};
+
+pub struct Map {
+    pub territories: UnGraph<Territory, Border>,
+    pub continents: Vec<Continent>,
+    // Where each territory is drawn, by territory index, if anywhere.
+    pub positions: Vec<Option<(f64, f64)>>,
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map { territories: UnGraph::new_undirected(), continents: Vec::new(), positions: Vec::new() }
+    }
+
+    pub fn default_map() -> Map {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let positions = vec![None; territories.node_count()];
+        Map { territories, continents, positions }
+    }
+
+    // Loads the map from either "default" or the path of a map file.
+    pub fn load(source: &str) -> Result<Map, String> {
+        match source {
+            "default" => Ok(Map::default_map()),
+            path => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| format!("could not read map file '{}': {}", path, e))?;
+                Map::parse(&contents)
+            }
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Map, String> {
+        let mut map = Map::new();
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (kind, arguments) = line.split_once(' ').unwrap_or((line, ""));
+            let arguments = arguments.trim();
+            match kind {
+                "continent" => {
+                    let (name, bonus) = arguments
+                        .rsplit_once(' ')
+                        .ok_or_else(|| format!("line {}: expected 'continent <name> <bonus>'", line_number))?;
+                    let name = name.trim();
+                    if map.continents.iter().any(|continent| continent.name == name) {
+                        return Err(format!("line {}: continent '{}' is defined twice", line_number, name));
+                    }
+                    let bonus = bonus
+                        .parse()
+                        .map_err(|_| format!("line {}: expected a number, found '{}'", line_number, bonus))?;
+                    map.continents.push(Continent { name: name.to_string(), bonus, territories: Vec::new() });
+                }
+                "territory" => {
+                    let (arguments, features) = match arguments.split_once(':') {
+                        Some((arguments, features)) => (arguments.trim(), Some(features)),
+                        None => (arguments, None),
+                    };
+                    let (name, position) = match arguments.split_once('@') {
+                        Some((name, position)) => (name.trim(), Some(parse_position(line_number, position)?)),
+                        None => (arguments, None),
+                    };
+                    if name.is_empty() {
+                        return Err(format!("line {}: expected 'territory <name>'", line_number));
+                    }
+                    if map.find_territory(name).is_some() {
+                        return Err(format!("line {}: territory '{}' is defined twice", line_number, name));
+                    }
+                    let territory_index = map.add_territory(name, position);
+                    if let Some(features) = features {
+                        let territory = &mut map.territories[NodeIndex::new(territory_index as usize)];
+                        set_features(territory, features).map_err(|e| format!("line {}: {}", line_number, e))?;
+                    }
+                    if let Some(continent) = map.continents.last_mut() {
+                        continent.territories.push(territory_index);
+                    }
+                }
+                "border" | "sea" => {
+                    let border_kind = if kind == "sea" { BorderKind::Sea } else { BorderKind::Land };
+                    let (first, second, is_one_way) = match arguments.split_once("->") {
+                        Some((from, to)) => (from, to, true),
+                        None => {
+                            let (first, second) = arguments.split_once(',').ok_or_else(|| format!(
+                                "line {}: expected '{} <territory>, <territory>' or '{} <from> -> <to>'",
+                                line_number, kind, kind))?;
+                            (first, second, false)
+                        }
+                    };
+                    let first_index = map.territory_index(line_number, first.trim())?;
+                    let second_index = map.territory_index(line_number, second.trim())?;
+                    if first_index == second_index {
+                        return Err(format!("line {}: '{}' cannot border itself", line_number, first.trim()));
+                    }
+                    map.territories.update_edge(
+                        NodeIndex::new(first_index as usize),
+                        NodeIndex::new(second_index as usize),
+                        Border { kind: border_kind, one_way_from: is_one_way.then_some(first_index) });
+                }
+                _ => return Err(format!("line {}: unknown line '{}', expected continent, territory, border or sea", line_number, kind)),
+            }
+        }
+
+        if map.territories.node_count() == 0 {
+            return Err("the map does not contain any territories".to_string());
+        }
+        Ok(map)
+    }
+
+    // Writes the map in the map file format, with territories grouped by
+    // continent.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let write_territory = |text: &mut String, territory_index: u32| {
+            text.push_str(&format!("territory {}", self.territories[NodeIndex::new(territory_index as usize)].name));
+            if let Some((x, y)) = self.positions[territory_index as usize] {
+                text.push_str(&format!(" @ {} {}", x, y));
+            }
+            let features = self.territories[NodeIndex::new(territory_index as usize)].features();
+            if !features.is_empty() {
+                text.push_str(&format!(": {}", features.join(", ")));
+            }
+            text.push('\n');
+        };
+
+        let other_territory_indices: Vec<u32> = (0..self.territories.node_count() as u32)
+            .filter(|territory_index| !self.continents.iter().any(|continent| continent.territories.contains(territory_index)))
+            .collect();
+        for territory_index in &other_territory_indices {
+            write_territory(&mut text, *territory_index);
+        }
+        if !other_territory_indices.is_empty() {
+            text.push('\n');
+        }
+        for continent in &self.continents {
+            text.push_str(&format!("continent {} {}\n", continent.name, continent.bonus));
+            for territory_index in &continent.territories {
+                write_territory(&mut text, *territory_index);
+            }
+            text.push('\n');
+        }
+        for edge in self.territories.edge_references() {
+            let border = edge.weight();
+            let kind = match border.kind {
+                BorderKind::Land => "border",
+                BorderKind::Sea => "sea",
+            };
+            match border.one_way_from {
+                Some(from) => {
+                    let to = if edge.source().index() as u32 == from { edge.target() } else { edge.source() };
+                    text.push_str(&format!("{} {} -> {}\n", kind, self.territories[NodeIndex::new(from as usize)].name, self.territories[to].name));
+                }
+                None => text.push_str(&format!("{} {}, {}\n", kind, self.territories[edge.source()].name, self.territories[edge.target()].name)),
+            }
+        }
+        text
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("could not write map file '{}': {}", path, e))
+    }
+
+    pub fn add_territory(&mut self, name: &str, position: Option<(f64, f64)>) -> u32 {
+        self.positions.push(position);
+        self.territories.add_node(Territory::new(name)).index() as u32
+    }
+
+    // Removes the territory along with its borders. The last territory takes
+    // over the index of the removed one, also as the start of its one-way routes.
+    pub fn remove_territory(&mut self, territory_index: u32) {
+        let last_index = self.territories.node_count() as u32 - 1;
+        self.territories.remove_node(NodeIndex::new(territory_index as usize));
+        self.positions.swap_remove(territory_index as usize);
+        for border in self.territories.edge_weights_mut() {
+            if border.one_way_from == Some(last_index) {
+                border.one_way_from = Some(territory_index);
+            }
+        }
+        for continent in &mut self.continents {
+            continent.territories.retain(|index| *index != territory_index);
+            for index in &mut continent.territories {
+                if *index == last_index {
+                    *index = territory_index;
+                }
+            }
+        }
+    }
+
+    pub fn find_territory(&self, name: &str) -> Option<u32> {
+        self.territories
+            .node_indices()
+            .find(|node_index| self.territories[*node_index].name == name)
+            .map(|node_index| node_index.index() as u32)
+    }
+
+    pub fn find_continent(&self, name: &str) -> Option<usize> {
+        self.continents.iter().position(|continent| continent.name == name)
+    }
+
+    fn territory_index(&self, line_number: usize, name: &str) -> Result<u32, String> {
+        self.find_territory(name)
+            .ok_or_else(|| format!("line {}: unknown territory '{}'", line_number, name))
+    }
+}
+
+pub fn parse_position(line_number: usize, position: &str) -> Result<(f64, f64), String> {
+    let coordinates: Vec<f64> = position
+        .split_whitespace()
+        .map(|coordinate| coordinate.parse())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| format!("line {}: expected a position '@ <x> <y>', found '@{}'", line_number, position))?;
+    match coordinates[..] {
+        [x, y] => Ok((x, y)),
+        _ => Err(format!("line {}: expected a position '@ <x> <y>', found '@{}'", line_number, position)),
+    }
+}
+
+// Sets the features of the territory from a list such as "mountains, fortress,
+// production 2". Features not listed are reset to those of plain territories,
+// and the territory is left unchanged if the list is invalid.
+pub fn set_features(territory: &mut Territory, features: &str) -> Result<(), String> {
+    let mut featured = Territory::new(&territory.name);
+    for feature in features.split(',').map(|feature| feature.trim()).filter(|feature| !feature.is_empty()) {
+        if let Some(terrain) = Terrain::from_name(feature) {
+            featured.terrain = terrain;
+        } else if feature == "fortress" {
+            featured.fortress = true;
+        } else if feature == "city" {
+            featured.city = true;
+        } else if feature == "bunker" {
+            featured.bunker = true;
+        } else if let Some(production) = feature.strip_prefix("production ") {
+            featured.production = production
+                .trim()
+                .parse()
+                .map_err(|_| format!("expected a production number, found '{}'", production.trim()))?;
+        } else {
+            return Err(format!("unknown feature '{}', expected plains, mountains, urban, fortress, city, bunker or production <number>", feature));
+        }
+    }
+    *territory = featured;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_are_saved_and_loaded_again() {
+        let map = Map::default_map();
+        let loaded = Map::parse(&map.to_text()).unwrap();
+
+        assert_eq!(loaded.territories.node_count(), map.territories.node_count());
+        assert_eq!(loaded.territories.edge_count(), map.territories.edge_count());
+        assert_eq!(loaded.continents.len(), 2);
+        assert_eq!(loaded.continents[1].name, "Asia");
+        assert_eq!(loaded.continents[1].bonus, 7);
+        assert_eq!(loaded.to_text(), map.to_text());
+
+        let loaded = Map::parse("continent North 5\nterritory Alaska @ 10 20.5\nterritory Alberta").unwrap();
+        assert_eq!(loaded.positions, vec![Some((10.0, 20.5)), None]);
+        assert_eq!(loaded.to_text(), "continent North 5\nterritory Alaska @ 10 20.5\nterritory Alberta\n\n");
+
+        // Territories in no continent come first.
+        let loaded = Map::parse("territory Hawaii\ncontinent North 5\nterritory Alaska\nborder Alaska, Hawaii").unwrap();
+        assert_eq!(loaded.continents[0].territories, vec![1]);
+        assert_eq!(loaded.to_text(), "territory Hawaii\n\ncontinent North 5\nterritory Alaska\n\nborder Alaska, Hawaii\n");
+
+        let loaded = Map::parse("continent North 5\nterritory Alaska\nterritory Hawaii\nsea Hawaii -> Alaska").unwrap();
+        let border = loaded.territories.edge_weights().next().unwrap();
+        assert_eq!(*border, Border { kind: BorderKind::Sea, one_way_from: Some(1) });
+        assert!(loaded.to_text().ends_with("\nsea Hawaii -> Alaska\n"));
+
+        let loaded = Map::parse("continent North 5\nterritory Alaska @ 10 20: mountains, fortress\nterritory Yukon: production 3").unwrap();
+        let alaska = &loaded.territories[NodeIndex::new(0)];
+        assert_eq!((alaska.name.as_str(), alaska.terrain, alaska.fortress, alaska.production), ("Alaska", Terrain::Mountains, true, 1));
+        assert_eq!(loaded.territories[NodeIndex::new(1)].production, 3);
+        assert_eq!(loaded.to_text(), "continent North 5\nterritory Alaska @ 10 20: mountains, fortress\nterritory Yukon: production 3\n\n");
+    }
+
+    #[test]
+    fn removing_a_territory_removes_its_borders() {
+        let mut map = Map::default_map();
+        let indonesia = map.find_territory("Indonesia").unwrap();
+        map.remove_territory(indonesia);
+
+        assert_eq!(map.territories.node_count(), 15);
+        assert_eq!(map.find_territory("Ural"), Some(indonesia));
+        assert_eq!(map.continents[0].territories, vec![0, 1, 2]);
+        assert!(map.continents[1].territories.contains(&indonesia));
+        let western_australia = NodeIndex::new(0);
+        assert_eq!(map.territories.neighbors(western_australia).count(), 1);
+
+        // Hawaii, the last territory, takes over Yukon's index and keeps its
+        // one-way route to Alaska.
+        let mut map = Map::parse("continent North 5\nterritory Alaska\nterritory Yukon\nterritory Hawaii\nborder Alaska, Yukon\nsea Hawaii -> Alaska").unwrap();
+        map.remove_territory(1);
+        let border = map.territories.edge_weights().next().unwrap();
+        assert_eq!(*border, Border { kind: BorderKind::Sea, one_way_from: Some(1) });
+        assert!(map.to_text().ends_with("\nsea Hawaii -> Alaska\n"));
+    }
+
+    #[test]
+    fn invalid_map_files_are_reported() {
+        let error = |contents: &str| Map::parse(contents).err().unwrap();
+        assert_eq!(error("continent North America five"), "line 1: expected a number, found 'five'");
+        assert_eq!(error("continent North 5\nterritory Alaska\nborder Alaska, Kamchatka"), "line 3: unknown territory 'Kamchatka'");
+        assert_eq!(error("continent North 5\nterritory Alaska\nterritory Alaska"), "line 3: territory 'Alaska' is defined twice");
+        assert_eq!(error("# empty"), "the map does not contain any territories");
+        assert_eq!(error("continent North 5\nterritory Alaska @ 10"), "line 2: expected a position '@ <x> <y>', found '@ 10'");
+        assert_eq!(error("continent North 5\nterritory Alaska: volcano"),
+            "line 2: unknown feature 'volcano', expected plains, mountains, urban, fortress, city, bunker or production <number>");
+    }
+}
@@ -0,0 +1,242 @@
+// Checks a map for mistakes, such as a territory nobody can reach or one that
+// belongs to no continent, and reports how its continents are shaped: which
+// territories border other continents, which ones cut the map in two if lost,
+// and how easy each continent is to hold.
+
+use petgraph::algo::articulation_points::articulation_points;
+use petgraph::algo::connected_components;
+use petgraph::graph::{NodeIndex, UnGraph};
+
+use crate::{crossable_borders, Border, Continent, Territory};
+
+// Returns every problem found with the map, or nothing if the map is playable.
+pub fn find_problems(territories: &UnGraph<Territory, Border>, continents: &[Continent]) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if territories.node_count() == 0 {
+        problems.push("The map has no territories.".to_string());
+        return problems;
+    }
+
+    for node_index in territories.node_indices() {
+        if territories.neighbors(node_index).next().is_none() {
+            problems.push(format!("{} has no neighbours.", territories[node_index].name));
+        }
+    }
+    let n_components = connected_components(territories);
+    if n_components > 1 {
+        problems.push(format!("The map falls apart into {} unconnected parts.", n_components));
+    } else {
+        // One-way routes must not trap armies: every territory has to be
+        // reachable from every other one.
+        let first_index = 0;
+        for territory_index in 1..territories.node_count() as u32 {
+            if !can_reach(territories, first_index, territory_index) {
+                problems.push(format!("{} cannot be reached from {} because of one-way routes.",
+                    territory_name(territories, territory_index), territory_name(territories, first_index)));
+            }
+            if !can_reach(territories, territory_index, first_index) {
+                problems.push(format!("{} cannot be reached from {} because of one-way routes.",
+                    territory_name(territories, first_index), territory_name(territories, territory_index)));
+            }
+        }
+    }
+
+    for node_index in territories.node_indices() {
+        let territory_index = node_index.index() as u32;
+        let continent_names: Vec<&str> = continents
+            .iter()
+            .filter(|continent| continent.territories.contains(&territory_index))
+            .map(|continent| continent.name.as_str())
+            .collect();
+        match continent_names.len() {
+            0 => problems.push(format!("{} is in no continent.", territories[node_index].name)),
+            1 => {}
+            _ => problems.push(format!("{} is in more than one continent: {}.", territories[node_index].name, continent_names.join(", "))),
+        }
+    }
+    for continent in continents {
+        if continent.territories.is_empty() {
+            problems.push(format!("{} has no territories.", continent.name));
+        }
+    }
+
+    problems
+}
+
+fn territory_name(territories: &UnGraph<Territory, Border>, territory_index: u32) -> &str {
+    &territories[NodeIndex::new(territory_index as usize)].name
+}
+
+// Whether armies can get from one territory to another, following one-way
+// routes only in their direction.
+fn can_reach(territories: &UnGraph<Territory, Border>, from_territory_index: u32, to_territory_index: u32) -> bool {
+    let mut visited = vec![false; territories.node_count()];
+    let mut stack = vec![from_territory_index];
+    visited[from_territory_index as usize] = true;
+    while let Some(territory_index) = stack.pop() {
+        if territory_index == to_territory_index {
+            return true;
+        }
+        for (neighbor_index, _) in crossable_borders(territories, territory_index) {
+            if !visited[neighbor_index as usize] {
+                visited[neighbor_index as usize] = true;
+                stack.push(neighbor_index);
+            }
+        }
+    }
+    false
+}
+
+// Territories of the continent with a neighbour outside it, in index order.
+pub fn border_territories(territories: &UnGraph<Territory, Border>, continent: &Continent) -> Vec<u32> {
+    let mut border_territory_indices: Vec<u32> = continent.territories
+        .iter()
+        .copied()
+        .filter(|territory_index| {
+            territories
+                .neighbors(NodeIndex::new(*territory_index as usize))
+                .any(|neighbor| !continent.territories.contains(&(neighbor.index() as u32)))
+        })
+        .collect();
+    border_territory_indices.sort();
+    border_territory_indices
+}
+
+// Number of borders through which the continent can be attacked from outside.
+fn entry_routes(territories: &UnGraph<Territory, Border>, continent: &Continent) -> usize {
+    continent.territories
+        .iter()
+        .flat_map(|territory_index| {
+            territories
+                .neighbors(NodeIndex::new(*territory_index as usize))
+                .map(move |neighbor| (neighbor.index() as u32, *territory_index))
+        })
+        .filter(|(neighbor_index, territory_index)| {
+            !continent.territories.contains(neighbor_index)
+                && crossable_borders(territories, *neighbor_index).iter().any(|(index, _)| index == territory_index)
+        })
+        .count()
+}
+
+// Territories whose loss splits the map in two, in index order.
+pub fn chokepoints(territories: &UnGraph<Territory, Border>) -> Vec<u32> {
+    // Whether a border is over land or sea makes no difference here.
+    let plain_territories = territories.map(|_, _| (), |_, _| ());
+    let mut chokepoint_indices: Vec<u32> = articulation_points(&plain_territories)
+        .into_iter()
+        .map(|node_index| node_index.index() as u32)
+        .collect();
+    chokepoint_indices.sort();
+    chokepoint_indices
+}
+
+// How many territories the holder of the continent gets per route into it that
+// they have to guard. Continents that score much higher than the others are
+// easy to hold and may deserve a smaller bonus.
+pub fn defensibility(territories: &UnGraph<Territory, Border>, continent: &Continent) -> f64 {
+    let n_entry_routes = entry_routes(territories, continent);
+    if n_entry_routes == 0 {
+        return continent.territories.len() as f64;
+    }
+    continent.territories.len() as f64 / n_entry_routes as f64
+}
+
+fn territory_names(territories: &UnGraph<Territory, Border>, territory_indices: &[u32]) -> String {
+    if territory_indices.is_empty() {
+        return "none".to_string();
+    }
+    territory_indices
+        .iter()
+        .map(|territory_index| territory_name(territories, *territory_index))
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+// Prints the problems and analysis of the map, and returns whether the map is
+// playable.
+pub fn print_report(territories: &UnGraph<Territory, Border>, continents: &[Continent]) -> bool {
+    println!("==== Map check ====");
+    let problems = find_problems(territories, continents);
+    if problems.is_empty() {
+        println!("No problems found.");
+    } else {
+        println!("{} problems found:", problems.len());
+        for problem in &problems {
+            println!("  {}", problem);
+        }
+    }
+    if territories.node_count() == 0 {
+        return false;
+    }
+
+    let degrees: Vec<usize> = territories
+        .node_indices()
+        .map(|node_index| territories.neighbors(node_index).count())
+        .collect();
+    println!("\n{} territories, {} borders, {} continents.", territories.node_count(), territories.edge_count(), continents.len());
+    println!("Neighbours per territory: min {}, max {}, average {:.2}.",
+        degrees.iter().min().unwrap(),
+        degrees.iter().max().unwrap(),
+        degrees.iter().sum::<usize>() as f64 / degrees.len() as f64);
+    println!("Chokepoints: {}.", territory_names(territories, &chokepoints(territories)));
+
+    println!("\n==== Continents ====");
+    for continent in continents {
+        println!("{} (bonus {}): {} territories, {} entry routes, defensibility {:.2}",
+            continent.name,
+            continent.bonus,
+            continent.territories.len(),
+            entry_routes(territories, continent),
+            defensibility(territories, continent));
+        println!("  Border territories: {}", territory_names(territories, &border_territories(territories, continent)));
+    }
+
+    problems.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_continents, setup_territories};
+
+    #[test]
+    fn default_map_is_playable() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        assert_eq!(find_problems(&territories, &continents), Vec::<String>::new());
+
+        // Australia is only reached through Indonesia, from Southeast Asia.
+        assert_eq!(border_territories(&territories, &continents[0]), vec![3]);
+        assert_eq!(defensibility(&territories, &continents[0]), 4.0);
+        assert!(chokepoints(&territories).contains(&3));
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        let mut territories = UnGraph::new_undirected();
+        let a = territories.add_node(Territory::new("A"));
+        let b = territories.add_node(Territory::new("B"));
+        territories.add_node(Territory::new("C"));
+        territories.add_edge(a, b, Border::land());
+        let continents = vec![
+            Continent { name: "North".to_string(), bonus: 1, territories: vec![0, 1] },
+            Continent { name: "South".to_string(), bonus: 1, territories: vec![1] },
+        ];
+
+        assert_eq!(find_problems(&territories, &continents), vec![
+            "C has no neighbours.",
+            "The map falls apart into 2 unconnected parts.",
+            "B is in more than one continent: North, South.",
+            "C is in no continent.",
+        ]);
+
+        let mut territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let edge = territories.find_edge(NodeIndex::new(3), NodeIndex::new(13)).unwrap();
+        territories[edge].one_way_from = Some(3);
+        let problems = find_problems(&territories, &continents);
+        assert_eq!(problems.len(), 12);
+        assert_eq!(problems[0], "Western Australia cannot be reached from India because of one-way routes.");
+    }
+}
@@ -0,0 +1,314 @@
+// Interactive map editor, started with "edit-map <file>". Designers add
+// territories, connect them and group them into continents one command at a
+// time, and see what is still wrong with the map after every change, instead of
+// writing Rust code or map files by hand.
+
+use std::path::Path;
+
+use petgraph::graph::NodeIndex;
+
+use crate::input::Input;
+use crate::map::{parse_position, set_features, Map};
+use crate::map_check;
+use crate::{print_all_territories, Border, BorderKind, Continent, Territory};
+
+const HELP: &str = "\
+Commands:
+  territory <name> [@ <x> <y>] [: <features>]
+                                   Add a territory, optionally at a position
+                                   and with features, e.g. \"mountains, fortress,
+                                   production 2\"
+  set <territory>: <features>      Change the features of a territory
+  continent <name> <bonus>         Add a continent, or change its bonus
+  assign <territory>, <continent>  Move a territory into a continent
+  connect <territory>, <territory>  Add a land border, or with \"<from> -> <to>\"
+                                   a one-way route
+  sea <territory>, <territory>     Add a sea border, or a one-way sea route
+  disconnect <territory>, <territory>
+  position <territory> @ <x> <y>   Set where the territory is drawn
+  rename <old name>, <new name>    Rename a territory or continent
+  delete <name>                    Delete a territory with its borders, or a
+                                   continent, leaving its territories in none
+  show                             Show the map
+  check                            Check the map and show how balanced it is
+  save [file]                      Save the map, by default to the file edited
+  load <file>                      Load a map, replacing the one edited
+  quit
+";
+
+// Edits the map in the given file, or a new map if the file does not exist yet.
+pub fn edit_map(path: &str, input: &mut Input) -> Result<(), String> {
+    let mut path = path.to_string();
+    let mut map = if Path::new(&path).exists() {
+        Map::load(&path)?
+    } else {
+        println!("Starting a new map, which will be saved to {}.", path);
+        Map::new()
+    };
+    print!("{}", HELP);
+
+    loop {
+        let line = input.prompt("edit", "\nmap> ");
+        let (command, arguments) = line.split_once(' ').unwrap_or((&line, ""));
+        let arguments = arguments.trim();
+
+        let result = match command {
+            "" => continue,
+            "help" => {
+                print!("{}", HELP);
+                continue;
+            }
+            "show" => {
+                print_map(&map);
+                continue;
+            }
+            "check" => {
+                map_check::print_report(&map.territories, &map.continents);
+                continue;
+            }
+            "quit" => return Ok(()),
+            "save" => {
+                if !arguments.is_empty() {
+                    path = arguments.to_string();
+                }
+                map.save(&path).map(|_| format!("Map saved to {}.", path))
+            }
+            "load" => Map::load(arguments).map(|loaded| {
+                map = loaded;
+                path = arguments.to_string();
+                format!("Map loaded from {}.", path)
+            }),
+            _ => apply_command(&mut map, command, arguments),
+        };
+
+        match result {
+            Ok(message) => {
+                println!("{}", message);
+                print_problems(&map);
+            }
+            Err(error) => input.reject(&error),
+        }
+    }
+}
+
+// Applies a command that changes the map, and describes what it did.
+fn apply_command(map: &mut Map, command: &str, arguments: &str) -> Result<String, String> {
+    match command {
+        "territory" => {
+            let (arguments, features) = match arguments.split_once(':') {
+                Some((arguments, features)) => (arguments.trim(), features),
+                None => (arguments, ""),
+            };
+            let (name, position) = match arguments.split_once('@') {
+                Some((name, position)) => (name.trim(), Some(parse_position_argument(position)?)),
+                None => (arguments, None),
+            };
+            check_new_name(map, name)?;
+            // Features are checked before the territory is added.
+            let mut featured = Territory::new("");
+            set_features(&mut featured, features)?;
+            let territory_index = map.add_territory(name, position);
+            let territory = &mut map.territories[NodeIndex::new(territory_index as usize)];
+            *territory = Territory { name: name.to_string(), ..featured };
+            Ok(format!("Added {}.", name))
+        }
+        "set" => {
+            let (name, features) = arguments.split_once(':').ok_or("Expected: set <territory>: <features>")?;
+            let territory_index = find_territory(map, name.trim())?;
+            let territory = &mut map.territories[NodeIndex::new(territory_index as usize)];
+            set_features(territory, features)?;
+            match territory.features() {
+                features if features.is_empty() => Ok(format!("{} is now plain.", territory.name)),
+                features => Ok(format!("{} now has: {}.", territory.name, features.join(", "))),
+            }
+        }
+        "continent" => {
+            let (name, bonus) = arguments.rsplit_once(' ').ok_or("Expected: continent <name> <bonus>")?;
+            let name = name.trim();
+            let bonus = bonus.parse().map_err(|_| format!("\"{}\" is not a number", bonus))?;
+            match map.find_continent(name) {
+                Some(continent_index) => {
+                    map.continents[continent_index].bonus = bonus;
+                    Ok(format!("{} is now worth {}.", name, bonus))
+                }
+                None => {
+                    check_new_name(map, name)?;
+                    map.continents.push(Continent { name: name.to_string(), bonus, territories: Vec::new() });
+                    Ok(format!("Added continent {}, worth {}.", name, bonus))
+                }
+            }
+        }
+        "assign" => {
+            let (territory_name, continent_name) = split_pair(arguments, "assign <territory>, <continent>")?;
+            let territory_index = find_territory(map, territory_name)?;
+            let continent_index = map.find_continent(continent_name)
+                .ok_or_else(|| format!("There is no continent called {}.", continent_name))?;
+            for continent in &mut map.continents {
+                continent.territories.retain(|index| *index != territory_index);
+            }
+            map.continents[continent_index].territories.push(territory_index);
+            Ok(format!("{} is now in {}.", territory_name, continent_name))
+        }
+        "connect" | "sea" => {
+            let kind = if command == "sea" { BorderKind::Sea } else { BorderKind::Land };
+            let (first_name, second_name, is_one_way) = match arguments.split_once("->") {
+                Some((from, to)) => (from.trim(), to.trim(), true),
+                None => {
+                    let (first_name, second_name) = split_pair(arguments, &format!("{} <territory>, <territory>", command))?;
+                    (first_name, second_name, false)
+                }
+            };
+            let first_index = find_territory(map, first_name)?;
+            let second_index = find_territory(map, second_name)?;
+            if first_index == second_index {
+                return Err(format!("{} cannot border itself.", first_name));
+            }
+            let border = Border { kind, one_way_from: is_one_way.then_some(first_index) };
+            map.territories.update_edge(NodeIndex::new(first_index as usize), NodeIndex::new(second_index as usize), border);
+            match (kind, is_one_way) {
+                (BorderKind::Land, false) => Ok(format!("{} and {} now border each other.", first_name, second_name)),
+                (BorderKind::Sea, false) => Ok(format!("{} and {} now border each other across the sea.", first_name, second_name)),
+                (_, true) => Ok(format!("{} can now be reached from {}, but not the other way.", second_name, first_name)),
+            }
+        }
+        "disconnect" => {
+            let (first_name, second_name) = split_pair(arguments, "disconnect <territory>, <territory>")?;
+            let first = NodeIndex::new(find_territory(map, first_name)? as usize);
+            let second = NodeIndex::new(find_territory(map, second_name)? as usize);
+            let edge = map.territories
+                .find_edge(first, second)
+                .ok_or_else(|| format!("{} and {} do not border each other.", first_name, second_name))?;
+            map.territories.remove_edge(edge);
+            Ok(format!("{} and {} no longer border each other.", first_name, second_name))
+        }
+        "position" => {
+            let (name, position) = arguments.split_once('@').ok_or("Expected: position <territory> @ <x> <y>")?;
+            let territory_index = find_territory(map, name.trim())?;
+            map.positions[territory_index as usize] = Some(parse_position_argument(position)?);
+            Ok(format!("Moved {}.", name.trim()))
+        }
+        "rename" => {
+            let (old_name, new_name) = split_pair(arguments, "rename <old name>, <new name>")?;
+            check_new_name(map, new_name)?;
+            if let Some(territory_index) = map.find_territory(old_name) {
+                map.territories[NodeIndex::new(territory_index as usize)].name = new_name.to_string();
+            } else if let Some(continent_index) = map.find_continent(old_name) {
+                map.continents[continent_index].name = new_name.to_string();
+            } else {
+                return Err(format!("There is no territory or continent called {}.", old_name));
+            }
+            Ok(format!("Renamed {} to {}.", old_name, new_name))
+        }
+        "delete" => {
+            if let Some(territory_index) = map.find_territory(arguments) {
+                let n_borders = map.territories.neighbors(NodeIndex::new(territory_index as usize)).count();
+                map.remove_territory(territory_index);
+                Ok(format!("Deleted {} and its {} borders.", arguments, n_borders))
+            } else if let Some(continent_index) = map.find_continent(arguments) {
+                map.continents.remove(continent_index);
+                Ok(format!("Deleted continent {}.", arguments))
+            } else {
+                Err(format!("There is no territory or continent called {}.", arguments))
+            }
+        }
+        _ => Err(format!("Unknown command \"{}\", type help to see the commands.", command)),
+    }
+}
+
+fn split_pair<'a>(arguments: &'a str, usage: &str) -> Result<(&'a str, &'a str), String> {
+    let (first, second) = arguments.split_once(',').ok_or_else(|| format!("Expected: {}", usage))?;
+    Ok((first.trim(), second.trim()))
+}
+
+fn find_territory(map: &Map, name: &str) -> Result<u32, String> {
+    map.find_territory(name).ok_or_else(|| format!("There is no territory called {}.", name))
+}
+
+// Territories and continents are looked up by name, so names have to be unique
+// among both, and cannot contain the characters that separate arguments.
+fn check_new_name(map: &Map, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(',') || name.contains('@') || name.contains(':') {
+        return Err(format!("\"{}\" cannot be used as a name.", name));
+    }
+    if map.find_territory(name).is_some() || map.find_continent(name).is_some() {
+        return Err(format!("The name {} is already taken.", name));
+    }
+    Ok(())
+}
+
+fn parse_position_argument(position: &str) -> Result<(f64, f64), String> {
+    parse_position(0, position).map_err(|_| format!("Expected a position \"@ <x> <y>\", found \"@{}\".", position))
+}
+
+fn print_map(map: &Map) {
+    print_all_territories(&map.territories, &[], None, false);
+    for continent in &map.continents {
+        let territory_names: Vec<&str> = continent.territories
+            .iter()
+            .map(|territory_index| map.territories[NodeIndex::new(*territory_index as usize)].name.as_str())
+            .collect();
+        println!("Continent {} (bonus {}): {}", continent.name, continent.bonus, territory_names.join(", "));
+    }
+}
+
+fn print_problems(map: &Map) {
+    let problems = map_check::find_problems(&map.territories, &map.continents);
+    if problems.is_empty() {
+        println!("The map is playable.");
+    } else {
+        println!("Problems left: {}, e.g. {}", problems.len(), problems[0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_are_built_and_saved_from_editor_commands() {
+        let path = std::env::temp_dir().join(format!("hazard_edit_map_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut input = Input::from_lines(&[
+            "continent North 3",
+            "territory Alaska @ 10 20",
+            "territory Alberta",
+            "territory Ontario: urban, production 3",
+            "assign Alaska, North",
+            "assign Alberta, North",
+            "assign Ontario, North",
+            "connect Alaska, Alberta",
+            "connect Alberta, Ontario",
+            "connect Alaska, Alaska",
+            "rename Ontario, Quebec",
+            "position Quebec @ 30 40",
+            "territory Hawaii",
+            "connect Hawaii, Alaska",
+            "delete Hawaii",
+            "disconnect Alaska, Alberta",
+            "connect Alaska, Quebec",
+            "sea Alberta -> Alaska",
+            "continent North 4",
+            "set Alaska: mountains, fortress",
+            "set Alberta: swamp",
+            "save",
+            "quit",
+        ]);
+        edit_map(path, &mut input).unwrap();
+
+        let map = Map::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(map_check::find_problems(&map.territories, &map.continents), Vec::<String>::new());
+        assert_eq!(map.to_text(), "\
+continent North 4
+territory Alaska @ 10 20: mountains, fortress
+territory Alberta
+territory Quebec @ 30 40: urban, production 3
+
+border Alberta, Quebec
+border Alaska, Quebec
+sea Alberta -> Alaska
+");
+    }
+}
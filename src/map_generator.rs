@@ -0,0 +1,235 @@
+// Generates random but playable maps. Territories are scattered over a square
+// and grouped into continents around spread-out centres, so that continents are
+// made of territories lying close together. Territories border their nearest
+// neighbours, which keeps every continent in one piece and the whole map
+// connected, and continents get a bonus that grows with their size and with the
+// number of their territories that have to be guarded.
+
+use std::collections::HashSet;
+
+use petgraph::graph::NodeIndex;
+use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::map::Map;
+use crate::map_check::border_territories;
+use crate::{Border, Continent};
+
+const NAME_STARTS: [&str; 24] = [
+    "Al", "Bar", "Cor", "Dal", "Est", "Fen", "Gal", "Hol", "Ir", "Jor", "Kes", "Lor",
+    "Mar", "Nor", "Or", "Pel", "Quar", "Ros", "Sar", "Tal", "Ul", "Ver", "Wes", "Zan",
+];
+const NAME_MIDDLES: [&str; 8] = ["", "a", "e", "i", "o", "an", "en", "ar"];
+const TERRITORY_NAME_ENDS: [&str; 12] = [
+    "ia", "mark", "holt", "wick", "dor", "mere", "vale", "stead", "heim", "gard", "ton", "moor",
+];
+const CONTINENT_NAME_ENDS: [&str; 4] = ["land", "ica", "asia", "ora"];
+
+// Territories are drawn at positions between 0 and this size.
+const MAP_SIZE: f64 = 1000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapSettings {
+    pub n_territories: usize,
+    pub n_continents: usize,
+    // Average number of neighbours per territory.
+    pub average_degree: f64,
+}
+
+impl Default for MapSettings {
+    fn default() -> Self {
+        MapSettings { n_territories: 24, n_continents: 4, average_degree: 3.0 }
+    }
+}
+
+impl MapSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.n_territories < 2 {
+            return Err("a map needs at least 2 territories".to_string());
+        }
+        if self.n_continents == 0 || self.n_continents > self.n_territories {
+            return Err(format!("the number of continents must be between 1 and {}", self.n_territories));
+        }
+        // Fewer borders than a tree has cannot connect the map, and each
+        // territory can border every other one at most.
+        let min_degree = 2.0 * (self.n_territories - 1) as f64 / self.n_territories as f64;
+        let max_degree = (self.n_territories - 1) as f64;
+        if self.average_degree < min_degree || self.average_degree > max_degree {
+            return Err(format!("the average number of neighbours must be between {:.2} and {}", min_degree, max_degree));
+        }
+        Ok(())
+    }
+}
+
+fn distance(first: (f64, f64), second: (f64, f64)) -> f64 {
+    ((first.0 - second.0).powi(2) + (first.1 - second.1).powi(2)).sqrt()
+}
+
+// Picks continent centres that are spread out: each next centre is the point
+// farthest away from the centres picked so far.
+fn pick_centres(points: &[(f64, f64)], n_continents: usize, rng: &mut StdRng) -> Vec<usize> {
+    let mut centres = vec![rng.gen_range(0..points.len())];
+    while centres.len() < n_continents {
+        let farthest = (0..points.len())
+            .filter(|point_index| !centres.contains(point_index))
+            .max_by(|first, second| {
+                let nearest_centre = |point_index: &usize| {
+                    centres.iter().map(|centre| distance(points[*point_index], points[*centre])).fold(f64::MAX, f64::min)
+                };
+                nearest_centre(first).total_cmp(&nearest_centre(second))
+            })
+            .unwrap();
+        centres.push(farthest);
+    }
+    centres
+}
+
+fn generate_name(ends: &[&str], used_names: &mut HashSet<String>, rng: &mut StdRng) -> String {
+    for _ in 0..100 {
+        let name = format!("{}{}{}",
+            NAME_STARTS.choose(rng).unwrap(),
+            NAME_MIDDLES.choose(rng).unwrap(),
+            ends.choose(rng).unwrap());
+        if used_names.insert(name.clone()) {
+            return name;
+        }
+    }
+    // Only very large maps run out of names.
+    let name = format!("{} {}", NAME_STARTS.choose(rng).unwrap(), used_names.len() + 1);
+    used_names.insert(name.clone());
+    name
+}
+
+// Finds the parent of a territory in a union-find forest.
+fn find_root(parents: &mut [usize], territory_index: usize) -> usize {
+    let mut root = territory_index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[territory_index] = root;
+    root
+}
+
+pub fn generate_map(settings: &MapSettings, rng: &mut StdRng) -> Result<Map, String> {
+    settings.validate()?;
+
+    let points: Vec<(f64, f64)> = (0..settings.n_territories).map(|_| (rng.gen::<f64>(), rng.gen::<f64>())).collect();
+    let centres = pick_centres(&points, settings.n_continents, rng);
+    let continent_of = |point: (f64, f64)| {
+        (0..centres.len())
+            .min_by(|first, second| distance(point, points[centres[*first]]).total_cmp(&distance(point, points[centres[*second]])))
+            .unwrap()
+    };
+
+    // Territories are numbered continent by continent, so that the map file
+    // lists each continent's territories together.
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by_key(|point_index| continent_of(points[*point_index]));
+    let points: Vec<(f64, f64)> = order.iter().map(|point_index| points[*point_index]).collect();
+
+    let mut used_names = HashSet::new();
+    let mut map = Map::new();
+    for _ in 0..settings.n_continents {
+        let name = generate_name(&CONTINENT_NAME_ENDS, &mut used_names, rng);
+        map.continents.push(Continent { name, bonus: 0, territories: Vec::new() });
+    }
+    for point in &points {
+        let name = generate_name(&TERRITORY_NAME_ENDS, &mut used_names, rng);
+        let position = ((point.0 * MAP_SIZE).round(), (point.1 * MAP_SIZE).round());
+        let territory_index = map.add_territory(&name, Some(position));
+        map.continents[continent_of(*point)].territories.push(territory_index);
+    }
+
+    // Every pair of territories, nearest first, with pairs in the same continent
+    // before pairs in different continents.
+    let mut pairs = Vec::new();
+    for first in 0..points.len() {
+        for second in first + 1..points.len() {
+            let same_continent = continent_of(points[first]) == continent_of(points[second]);
+            pairs.push((!same_continent, distance(points[first], points[second]), first, second));
+        }
+    }
+    pairs.sort_by(|first, second| first.0.cmp(&second.0).then(first.1.total_cmp(&second.1)));
+
+    // The shortest borders that connect the map come first, which connects each
+    // continent within itself before linking it to the others.
+    let mut parents: Vec<usize> = (0..points.len()).collect();
+    for (_, _, first, second) in &pairs {
+        let first_root = find_root(&mut parents, *first);
+        let second_root = find_root(&mut parents, *second);
+        if first_root != second_root {
+            parents[first_root] = second_root;
+            map.territories.add_edge(NodeIndex::new(*first), NodeIndex::new(*second), Border::land());
+        }
+    }
+
+    // Then the shortest remaining borders, regardless of continent, until
+    // territories have the requested number of neighbours on average.
+    let n_borders = (settings.average_degree * points.len() as f64 / 2.0).round() as usize;
+    let mut remaining_pairs: Vec<(f64, usize, usize)> = pairs
+        .iter()
+        .map(|(_, pair_distance, first, second)| (*pair_distance, *first, *second))
+        .collect();
+    remaining_pairs.sort_by(|first, second| first.0.total_cmp(&second.0));
+    for (_, first, second) in remaining_pairs {
+        if map.territories.edge_count() >= n_borders {
+            break;
+        }
+        map.territories.update_edge(NodeIndex::new(first), NodeIndex::new(second), Border::land());
+    }
+
+    // A continent is worth more the bigger it is, and the more of its
+    // territories can be attacked from outside.
+    for continent_index in 0..map.continents.len() {
+        let continent = &map.continents[continent_index];
+        let n_border_territories = border_territories(&map.territories, continent).len();
+        map.continents[continent_index].bonus = ((continent.territories.len() + 2 * n_border_territories) as u32 / 3).max(1);
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map_check::find_problems;
+    use rand::SeedableRng;
+
+    #[test]
+    fn generated_maps_are_playable() {
+        for seed in 0..20 {
+            let settings = MapSettings { n_territories: 30, n_continents: 5, average_degree: 3.5 };
+            let map = generate_map(&settings, &mut StdRng::seed_from_u64(seed)).unwrap();
+
+            assert_eq!(map.territories.node_count(), 30);
+            assert_eq!(map.territories.edge_count(), 53);
+            assert_eq!(map.continents.len(), 5);
+            assert_eq!(find_problems(&map.territories, &map.continents), Vec::<String>::new());
+            assert!(map.continents.iter().all(|continent| continent.bonus >= 1));
+
+            // Every continent is in one piece.
+            for continent in &map.continents {
+                let continent_territories = map.territories.filter_map(
+                    |node_index, _| continent.territories.contains(&(node_index.index() as u32)).then_some(()),
+                    |_, _| Some(()));
+                assert_eq!(petgraph::algo::connected_components(&continent_territories), 1);
+            }
+
+            // Saving and loading the map gives the same map.
+            assert_eq!(Map::parse(&map.to_text()).unwrap().to_text(), map.to_text());
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_map() {
+        let settings = MapSettings::default();
+        let first = generate_map(&settings, &mut StdRng::seed_from_u64(7)).unwrap();
+        let second = generate_map(&settings, &mut StdRng::seed_from_u64(7)).unwrap();
+        assert_eq!(first.to_text(), second.to_text());
+
+        let too_few_borders = MapSettings { average_degree: 1.0, ..settings };
+        assert_eq!(generate_map(&too_few_borders, &mut StdRng::seed_from_u64(7)).err().unwrap(),
+            "the average number of neighbours must be between 1.92 and 23");
+    }
+}
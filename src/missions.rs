@@ -0,0 +1,420 @@
+// Secret mission objectives. Missions are defined in a deck file, with a default
+// deck built in, and are adapted to the loaded map and the players in the game
+// when they are dealt.
+
+use std::fs;
+
+use petgraph::graph::UnGraph;
+use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
+
+use crate::{teams, Border, Color, Continent, Player, Territory};
+
+const DEFAULT_MISSION_DECK: &str = include_str!("../data/missions.txt");
+
+#[derive(Clone, Debug)]
+pub enum TerritoryCount {
+    Absolute(u32),
+    Percent(u32),
+}
+
+// A mission as written in the deck, before it is adapted to the map.
+#[derive(Clone, Debug)]
+pub enum MissionTemplate {
+    HoldContinents(Vec<String>),
+    HoldTerritories { count: TerritoryCount, min_armies: u32 },
+    Eliminate(Color),
+}
+
+pub struct MissionDeck {
+    pub missions: Vec<MissionTemplate>,
+    pub fallback: Option<MissionTemplate>,
+}
+
+// A mission adapted to the loaded map, as held by a player.
+#[derive(Clone, Debug)]
+pub enum Mission {
+    // Indices into the list of continents.
+    HoldContinents(Vec<usize>),
+    HoldTerritories { count: u32, min_armies: u32 },
+    // The fallback mission takes over if the target is eliminated by someone else.
+    Eliminate { target: Color, fallback: Box<Mission> },
+}
+
+impl MissionDeck {
+    // Loads the deck from the given file, or the built-in deck if there is none.
+    pub fn load(path: Option<&str>) -> Result<MissionDeck, String> {
+        match path {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| format!("could not read mission deck '{}': {}", path, e))?;
+                MissionDeck::parse(&contents)
+            }
+            None => MissionDeck::parse(DEFAULT_MISSION_DECK),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<MissionDeck, String> {
+        let mut deck = MissionDeck {
+            missions: Vec::new(),
+            fallback: None,
+        };
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.strip_prefix("fallback ") {
+                Some(fallback) => {
+                    let fallback = parse_mission(line_number, fallback.trim())?;
+                    if let MissionTemplate::Eliminate(_) = fallback {
+                        return Err(format!("line {}: the fallback mission cannot be an eliminate mission", line_number));
+                    }
+                    deck.fallback = Some(fallback);
+                }
+                None => deck.missions.push(parse_mission(line_number, line)?),
+            }
+        }
+
+        if deck.missions.is_empty() {
+            return Err("the mission deck does not contain any missions".to_string());
+        }
+        let has_eliminate_missions = deck.missions
+            .iter()
+            .any(|mission| matches!(mission, MissionTemplate::Eliminate(_)));
+        if has_eliminate_missions && deck.fallback.is_none() {
+            return Err("the mission deck has eliminate missions, so it needs a fallback mission".to_string());
+        }
+
+        Ok(deck)
+    }
+}
+
+fn parse_mission(line_number: usize, line: &str) -> Result<MissionTemplate, String> {
+    let (kind, arguments) = line.split_once(' ').unwrap_or((line, ""));
+    let arguments = arguments.trim();
+
+    match kind {
+        "continents" => {
+            let names: Vec<String> = arguments
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+            if names.is_empty() {
+                return Err(format!("line {}: 'continents' needs at least one continent name", line_number));
+            }
+            Ok(MissionTemplate::HoldContinents(names))
+        }
+        "territories" => {
+            let parts: Vec<&str> = arguments.split_whitespace().collect();
+            if parts.len() != 2 {
+                return Err(format!("line {}: expected 'territories <count or percent> <armies>'", line_number));
+            }
+            let count = match parts[0].strip_suffix('%') {
+                Some(percent) => TerritoryCount::Percent(parse_number(line_number, percent)?),
+                None => TerritoryCount::Absolute(parse_number(line_number, parts[0])?),
+            };
+            let min_armies = parse_number(line_number, parts[1])?;
+            if min_armies == 0 {
+                return Err(format!("line {}: territories need at least 1 army each", line_number));
+            }
+            Ok(MissionTemplate::HoldTerritories { count, min_armies })
+        }
+        "eliminate" => {
+            let target = Color::from_name(arguments)
+                .ok_or_else(|| format!("line {}: unknown color '{}'", line_number, arguments))?;
+            Ok(MissionTemplate::Eliminate(target))
+        }
+        _ => Err(format!("line {}: unknown mission '{}', expected continents, territories or eliminate", line_number, kind)),
+    }
+}
+
+fn parse_number(line_number: usize, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("line {}: expected a number, found '{}'", line_number, value))
+}
+
+impl MissionTemplate {
+    // Adapts the mission to the loaded map, or returns None if the map cannot
+    // support it, e.g. because a named continent does not exist. Eliminate
+    // missions are adapted when dealing, since they depend on the players.
+    fn adapt_to_map(
+        &self,
+        territories: &UnGraph<Territory, Border>,
+        continents: &[Continent]) -> Option<Mission> {
+        match self {
+            MissionTemplate::HoldContinents(names) => {
+                let continent_indices = names
+                    .iter()
+                    .map(|name| continents.iter().position(|continent| continent.name == *name))
+                    .collect::<Option<Vec<usize>>>()?;
+                Some(Mission::HoldContinents(continent_indices))
+            }
+            MissionTemplate::HoldTerritories { count, min_armies } => {
+                let total_territories = territories.node_count() as u32;
+                let count = match count {
+                    TerritoryCount::Absolute(count) => *count,
+                    TerritoryCount::Percent(percent) => (total_territories * percent).div_ceil(100),
+                };
+                if count == 0 || count > total_territories {
+                    return None;
+                }
+                Some(Mission::HoldTerritories { count, min_armies: *min_armies })
+            }
+            MissionTemplate::Eliminate(_) => None,
+        }
+    }
+}
+
+// Reads a mission written as in the deck, e.g. "continents Australia", as the
+// goal of a single player, such as the victory condition of a scenario. Eliminate
+// missions fall back to conquering the world if someone else eliminates the target.
+pub fn parse_player_mission(
+    line_number: usize,
+    text: &str,
+    territories: &UnGraph<Territory, Border>,
+    continents: &[Continent]) -> Result<Mission, String> {
+    match parse_mission(line_number, text)? {
+        MissionTemplate::Eliminate(target) => {
+            let conquer_the_world = Mission::HoldTerritories { count: territories.node_count() as u32, min_armies: 1 };
+            Ok(Mission::Eliminate { target, fallback: Box::new(conquer_the_world) })
+        }
+        template => template
+            .adapt_to_map(territories, continents)
+            .ok_or_else(|| format!("line {}: the mission '{}' does not fit the map", line_number, text)),
+    }
+}
+
+// Deals one mission from the shuffled deck to every player except the neutral
+// player. Missions that do not fit the map are skipped, and eliminate missions
+// whose target is the player themselves or not in the game are replaced by the
+// fallback mission.
+pub fn deal_missions(
+    deck: &MissionDeck,
+    players: &mut [Player],
+    territories: &UnGraph<Territory, Border>,
+    continents: &[Continent],
+    rng: &mut StdRng) {
+    let fallback = deck.fallback
+        .as_ref()
+        .and_then(|fallback| fallback.adapt_to_map(territories, continents));
+
+    let mut shuffled_missions = deck.missions.clone();
+    shuffled_missions.shuffle(rng);
+    let mut shuffled_missions = shuffled_missions.into_iter();
+
+    for player_idx in 0..players.len() {
+        if players[player_idx].is_neutral {
+            continue;
+        }
+
+        let mut mission = None;
+        for template in shuffled_missions.by_ref() {
+            mission = match template {
+                MissionTemplate::Eliminate(target) => {
+                    // Players are never asked to eliminate themselves or a teammate.
+                    let target_in_game = players
+                        .iter()
+                        .enumerate()
+                        .any(|(other_idx, other)| {
+                            !teams::are_allies(players, player_idx, other_idx) && !other.is_neutral && other.color == target
+                        });
+                    match (target_in_game, &fallback) {
+                        (true, Some(fallback)) => Some(Mission::Eliminate { target, fallback: Box::new(fallback.clone()) }),
+                        (false, Some(fallback)) => Some(fallback.clone()),
+                        (_, None) => None,
+                    }
+                }
+                template => template.adapt_to_map(territories, continents),
+            };
+            if mission.is_some() {
+                break;
+            }
+        }
+
+        if mission.is_none() {
+            println!("The mission deck has run out, player {} will have to conquer the world instead.", players[player_idx].name);
+        }
+        players[player_idx].mission = mission;
+    }
+    println!("Secret missions have been dealt.");
+}
+
+pub fn describe_mission(mission: &Mission, continents: &[Continent]) -> String {
+    match mission {
+        Mission::HoldContinents(continent_indices) => {
+            let names: Vec<&str> = continent_indices
+                .iter()
+                .map(|continent_index| continents[*continent_index].name.as_str())
+                .collect();
+            format!("Hold all of {}", names.join(" and "))
+        }
+        Mission::HoldTerritories { count, min_armies: 1 } => {
+            format!("Own at least {} territories", count)
+        }
+        Mission::HoldTerritories { count, min_armies } => {
+            format!("Own at least {} territories with at least {} armies each", count, min_armies)
+        }
+        Mission::Eliminate { target, .. } => {
+            format!("Eliminate the {} player", target.name())
+        }
+    }
+}
+
+pub fn is_mission_complete(
+    mission: &Mission,
+    player_idx: usize,
+    players: &[Player],
+    continents: &[Continent],
+    attacker_idx: usize) -> bool {
+    let player = &players[player_idx];
+    match mission {
+        Mission::HoldContinents(continent_indices) => continent_indices
+            .iter()
+            .all(|continent_index| continents[*continent_index]
+                .territories
+                .iter()
+                .all(|territory_index| player.army_per_territory.contains_key(territory_index))),
+        Mission::HoldTerritories { count, min_armies } => {
+            let n_territories = player.army_per_territory
+                .values()
+                .filter(|armies| **armies >= *min_armies)
+                .count();
+            n_territories as u32 >= *count
+        }
+        Mission::Eliminate { target, .. } => {
+            player_idx == attacker_idx && is_color_eliminated(players, target)
+        }
+    }
+}
+
+fn is_color_eliminated(players: &[Player], color: &Color) -> bool {
+    players
+        .iter()
+        .filter(|player| !player.is_neutral && player.color == *color)
+        .all(|player| player.army_per_territory.is_empty())
+}
+
+// Evaluated after every attack, so that conquests and eliminations are noticed
+// straight away. Eliminate missions whose target was just eliminated by another
+// player switch to their fallback mission. Returns the index of a player whose
+// mission is complete, checking the attacker first.
+pub fn check_missions(
+    players: &mut [Player],
+    continents: &[Continent],
+    attacker_idx: usize) -> Option<usize> {
+    for player_idx in 0..players.len() {
+        let fallback = match &players[player_idx].mission {
+            Some(Mission::Eliminate { target, fallback })
+                if player_idx != attacker_idx && is_color_eliminated(players, target) => (**fallback).clone(),
+            _ => continue,
+        };
+        println!("Player {}'s target has been eliminated by someone else, their secret mission has changed.",
+            players[player_idx].name);
+        players[player_idx].mission = Some(fallback);
+    }
+
+    let check_order = std::iter::once(attacker_idx)
+        .chain((0..players.len()).filter(|player_idx| *player_idx != attacker_idx));
+    for player_idx in check_order {
+        if let Some(mission) = &players[player_idx].mission {
+            if is_mission_complete(mission, player_idx, players, continents, attacker_idx) {
+                return Some(player_idx);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_continents, setup_territories};
+    use rand::SeedableRng;
+
+    #[test]
+    fn default_deck_parses() {
+        let deck = MissionDeck::load(None).unwrap();
+        assert!(!deck.missions.is_empty());
+        assert!(deck.fallback.is_some());
+    }
+
+    #[test]
+    fn deck_with_eliminate_missions_needs_a_fallback() {
+        assert!(MissionDeck::parse("eliminate Red\n").is_err());
+        assert!(MissionDeck::parse("eliminate Purple\nfallback territories 50% 1\n").is_err());
+        assert!(MissionDeck::parse("eliminate Red\nfallback territories 50% 1\n").is_ok());
+    }
+
+    #[test]
+    fn missions_are_adapted_to_the_map() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+
+        let missing_continent = MissionTemplate::HoldContinents(vec!["Europe".to_string(), "Asia".to_string()]);
+        assert!(missing_continent.adapt_to_map(&territories, &continents).is_none());
+
+        let half_the_map = MissionTemplate::HoldTerritories { count: TerritoryCount::Percent(50), min_armies: 2 };
+        match half_the_map.adapt_to_map(&territories, &continents) {
+            Some(Mission::HoldTerritories { count, min_armies }) => {
+                assert_eq!(count, 8);
+                assert_eq!(min_armies, 2);
+            }
+            mission => panic!("Unexpected mission {:?}", mission),
+        }
+    }
+
+    #[test]
+    fn eliminate_mission_falls_back_when_someone_else_eliminates_the_target() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+        ];
+        players[0].army_per_territory.insert(0, 1);
+        players[2].army_per_territory.insert(1, 1);
+        players[0].mission = Some(Mission::Eliminate {
+            target: Color::Blue,
+            fallback: Box::new(Mission::HoldTerritories { count: 2, min_armies: 1 }),
+        });
+
+        // Cy has just eliminated Bo, so Ann's mission can no longer be completed.
+        assert_eq!(check_missions(&mut players, &continents, 2), None);
+        assert!(matches!(players[0].mission, Some(Mission::HoldTerritories { count: 2, .. })));
+
+        players[0].army_per_territory.insert(2, 1);
+        assert_eq!(check_missions(&mut players, &continents, 0), Some(0));
+    }
+
+    #[test]
+    fn nobody_is_dealt_their_own_team_to_eliminate() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+            Player::new("Di".to_string(), Color::Yellow),
+        ];
+        for (player, team) in players.iter_mut().zip([1, 2, 1, 2]) {
+            player.team = Some(team);
+        }
+
+        // Green plays with Ann, so only Bo and Di keep the mission.
+        let deck = MissionDeck::parse("eliminate Green\neliminate Green\neliminate Green\neliminate Green\nfallback territories 10 1\n").unwrap();
+        deal_missions(&deck, &mut players, &territories, &continents, &mut StdRng::seed_from_u64(0));
+        let is_eliminate: Vec<bool> = players
+            .iter()
+            .map(|player| matches!(player.mission, Some(Mission::Eliminate { target: Color::Green, .. })))
+            .collect();
+        assert_eq!(is_eliminate, vec![false, true, false, true]);
+        assert!(matches!(players[0].mission, Some(Mission::HoldTerritories { count: 10, .. })));
+    }
+}
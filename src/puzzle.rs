@@ -0,0 +1,509 @@
+// Puzzles, played with "puzzle <file>", are win-in-one-turn challenges such as
+// "conquer all of Australia this turn" or "eliminate Blue". A puzzle file is a
+// scenario file whose first player has to complete their victory condition
+// within a single attack phase:
+//
+//     description Take Australia before Bo reinforces.
+//     player Ann
+//     player Bo, Blue
+//     hold Western Australia, Ann, 6
+//     ...
+//     victory Ann, continents Australia
+//     dice 6 5 2 3 1
+//
+// The dice are rolled from "--seed", or repeat the rolls on a "dice" line. Each
+// attack goes on until the target is conquered or the attacker cannot continue.
+// Afterwards the solver shows the best line of attacks, with its chance of
+// success. It searches every sequence of attacks, moving in either the fewest
+// or the most armies after a conquest, and remembers the chance from each
+// position it reaches so that attacks made in a different order are only
+// searched once. The number of positions still grows exponentially with the
+// number of attacks and armies, so puzzles are meant to be small.
+
+use std::collections::HashMap;
+
+use petgraph::graph::{NodeIndex, UnGraph};
+
+use crate::combat::{defence_of, perform_attack, raise_roll, Dice};
+use crate::economy::hand_over_buildings;
+use crate::input::Input;
+use crate::map::Map;
+use crate::missions::{describe_mission, is_mission_complete, Mission};
+use crate::rules::RuleSet;
+use crate::scenario::Scenario;
+use crate::undo::History;
+use crate::{crossable_borders, print_all_territories, print_player, teams, territory_owner, Border, Continent, Player, Territory};
+
+pub struct Puzzle {
+    pub scenario: Scenario,
+    // Rolls repeated in order instead of random dice, if given.
+    pub dice: Option<Vec<u8>>,
+}
+
+impl Puzzle {
+    pub fn load(path: &str, map: &Map, rules: &RuleSet) -> Result<Puzzle, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read puzzle file '{}': {}", path, e))?;
+        Puzzle::parse(&contents, map, rules)
+    }
+
+    pub fn parse(contents: &str, map: &Map, rules: &RuleSet) -> Result<Puzzle, String> {
+        // The dice line is taken out before the rest is read as a scenario, with
+        // an empty line in its place to keep the line numbers right.
+        let mut dice = None;
+        let mut scenario_lines = Vec::new();
+        for (line_index, line) in contents.lines().enumerate() {
+            match line.trim().strip_prefix("dice ") {
+                Some(rolls) => {
+                    let rolls = rolls
+                        .split_whitespace()
+                        .map(|roll| roll.parse::<u8>().ok().filter(|roll| (1..=6).contains(roll)))
+                        .collect::<Option<Vec<u8>>>()
+                        .filter(|rolls| !rolls.is_empty())
+                        .ok_or_else(|| format!("line {}: expected 'dice' followed by rolls from 1 to 6", line_index + 1))?;
+                    dice = Some(rolls);
+                    scenario_lines.push("");
+                }
+                None => scenario_lines.push(line),
+            }
+        }
+
+        let scenario = Scenario::parse(&scenario_lines.join("\n"), map, rules)?;
+        if scenario.players[0].mission.is_none() {
+            return Err(format!("the puzzle needs a victory condition for {}, who moves first", scenario.players[0].name));
+        }
+        Ok(Puzzle { scenario, dice })
+    }
+
+    fn goal(&self) -> &Mission {
+        self.scenario.players[0].mission.as_ref().unwrap()
+    }
+}
+
+// Lets the first player attempt the puzzle, then shows how the solver would
+// have played it. Returns true if the puzzle was solved.
+pub fn play(puzzle: &Puzzle, map: &Map, rules: &RuleSet, input: &mut Input, dice: &mut dyn Dice) -> bool {
+    let territories = &map.territories;
+    let mut players = puzzle.scenario.players.clone();
+
+    println!("==== Puzzle ====");
+    for line in &puzzle.scenario.description {
+        println!("{}", line);
+    }
+    println!("{}, in a single turn: {}.", players[0].name, describe_mission(puzzle.goal(), &map.continents));
+    print_all_territories(territories, &players, Some(0), false);
+
+    let is_solved = |players: &[Player]| is_mission_complete(puzzle.goal(), 0, players, &map.continents, 0);
+    let mut history = History::new();
+    while !is_solved(&players) {
+        print_player(territories, &players, 0, Some(0), false);
+        let attacks = possible_attacks(territories, &players, rules);
+        if attacks.is_empty() {
+            println!("There are no attacks left to make.");
+            break;
+        }
+        println!("Possible attacks:");
+        for (from, to, _) in &attacks {
+            println!("  From {} ({}) to {} ({})", from, territory_name(territories, *from), to, territory_name(territories, *to));
+        }
+
+        let attack_any = input.prompt_yes_no("attack", "Do you want to attack any territory? (y/n): ");
+        if !matches!(attack_any.as_str(), "y" | "Y") {
+            break;
+        }
+        let from = input.prompt_number("attack", "Attacking from territory index: ");
+        let to = input.prompt_number("attack", "Targeting territory index: ");
+        let Some((_, _, defender_idx)) = attacks.iter().find(|(attack_from, attack_to, _)| *attack_from == from && *attack_to == to) else {
+            input.reject("This attack is not possible.");
+            continue;
+        };
+        while !perform_attack(territories, &mut players, 0, *defender_idx, from, to, rules, &mut history, input, dice) {}
+        println!();
+    }
+
+    let solved = is_solved(&players);
+    if solved {
+        println!("\nPuzzle solved!");
+    } else {
+        println!("\nThe puzzle is not solved.");
+    }
+
+    let mut solver = Solver::new(territories, &map.continents, rules, puzzle.goal());
+    let chance = solver.success_chance(&puzzle.scenario.players);
+    println!("The best line of attacks solves the puzzle {:.1}% of the time:", chance * 100.0);
+    for step in solver.best_line(&puzzle.scenario.players) {
+        println!("  {}", step);
+    }
+    solved
+}
+
+fn territory_name(territories: &UnGraph<Territory, Border>, territory_index: u32) -> &str {
+    &territories[NodeIndex::new(territory_index as usize)].name
+}
+
+// Every attack the first player can make, as (from, to, defender index), in
+// order of territory index.
+fn possible_attacks(territories: &UnGraph<Territory, Border>, players: &[Player], rules: &RuleSet) -> Vec<(u32, u32, usize)> {
+    let mut attacks = Vec::new();
+    for (from, armies) in &players[0].army_per_territory {
+        if *armies <= rules.armies_left_behind {
+            continue;
+        }
+        for (to, _) in crossable_borders(territories, *from) {
+            if let Some(defender_idx) = territory_owner(players, to) {
+                if !teams::are_allies(players, 0, defender_idx) {
+                    attacks.push((*from, to, defender_idx));
+                }
+            }
+        }
+    }
+    attacks.sort();
+    attacks
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum BattleOutcome {
+    // The attacker's armies left in the attacking territory, and the fewest
+    // they have to move in.
+    Conquered { attackers_left: u32, min_move: u32 },
+    Repelled { defenders_left: u32 },
+}
+
+// Armies lost by the attacker and the defender in a round.
+type Losses = (u32, u32);
+// Armies in the attacking and defending territories, the most dice each side
+// can roll and the defence bonus.
+type Battle = (u32, u32, u32, u32, u32);
+
+struct Solver<'a> {
+    territories: &'a UnGraph<Territory, Border>,
+    continents: &'a [Continent],
+    rules: &'a RuleSet,
+    goal: &'a Mission,
+    // Chances of the losses of a round, by number of dice and defence bonus.
+    rounds: HashMap<(u32, u32, u32), Vec<(Losses, f64)>>,
+    // Chances of the outcomes of a whole attack.
+    battles: HashMap<Battle, Vec<(BattleOutcome, f64)>>,
+    // Chance of solving the puzzle from each position reached.
+    chances: HashMap<Vec<(u32, usize, u32)>, f64>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(territories: &'a UnGraph<Territory, Border>, continents: &'a [Continent], rules: &'a RuleSet, goal: &'a Mission) -> Self {
+        Solver { territories, continents, rules, goal, rounds: HashMap::new(), battles: HashMap::new(), chances: HashMap::new() }
+    }
+
+    fn is_solved(&self, players: &[Player]) -> bool {
+        is_mission_complete(self.goal, 0, players, self.continents, 0)
+    }
+
+    // Chance of solving the puzzle from the position with the best attacks.
+    fn success_chance(&mut self, players: &[Player]) -> f64 {
+        if self.is_solved(players) {
+            return 1.0;
+        }
+        let key = position_key(players);
+        if let Some(chance) = self.chances.get(&key) {
+            return *chance;
+        }
+        let chance = possible_attacks(self.territories, players, self.rules)
+            .into_iter()
+            .map(|(from, to, defender_idx)| self.attack_chance(players, from, to, defender_idx))
+            .fold(0.0, f64::max);
+        self.chances.insert(key, chance);
+        chance
+    }
+
+    // Chance of solving the puzzle when making this attack, and then playing on
+    // as well as possible.
+    fn attack_chance(&mut self, players: &[Player], from: u32, to: u32, defender_idx: usize) -> f64 {
+        self.positions_after(players, from, to, defender_idx)
+            .into_iter()
+            .map(|(positions, chance)| {
+                chance * positions.iter().map(|position| self.success_chance(position)).fold(0.0, f64::max)
+            })
+            .sum()
+    }
+
+    // The positions each outcome of an attack leads to, with the chance of the
+    // outcome. A conquest leads to one position for moving in the fewest armies
+    // and one for moving in the most.
+    fn positions_after(&mut self, players: &[Player], from: u32, to: u32, defender_idx: usize) -> Vec<(Vec<Vec<Player>>, f64)> {
+        let border = self.territories
+            .find_edge(NodeIndex::new(from as usize), NodeIndex::new(to as usize))
+            .map(|edge| self.territories[edge])
+            .unwrap();
+        let (is_fortified, defence_bonus, _) = defence_of(self.territories, &players[defender_idx], to, self.rules);
+        let outcomes = self.battle_outcomes(
+            players[0].army_per_territory[&from],
+            players[defender_idx].army_per_territory[&to],
+            self.rules.max_attack_dice_across(border.kind),
+            self.rules.max_defend_dice_with(is_fortified),
+            defence_bonus);
+
+        let left_behind = self.rules.armies_left_behind;
+        outcomes
+            .into_iter()
+            .map(|(outcome, chance)| {
+                let mut position = players.to_vec();
+                let positions = match outcome {
+                    BattleOutcome::Conquered { attackers_left, min_move } => {
+                        position[defender_idx].army_per_territory.remove(&to);
+                        hand_over_buildings(&mut position, defender_idx, 0, to);
+                        let mut moves = vec![min_move, attackers_left - left_behind];
+                        moves.dedup();
+                        moves
+                            .into_iter()
+                            .map(|n_moved| {
+                                let mut position = position.clone();
+                                position[0].army_per_territory.insert(from, attackers_left - n_moved);
+                                position[0].army_per_territory.insert(to, n_moved);
+                                position
+                            })
+                            .collect()
+                    }
+                    BattleOutcome::Repelled { defenders_left } => {
+                        position[0].army_per_territory.insert(from, left_behind);
+                        position[defender_idx].army_per_territory.insert(to, defenders_left);
+                        vec![position]
+                    }
+                };
+                (positions, chance)
+            })
+            .collect()
+    }
+
+    // Chances of every way an attack can end when it goes on until the target
+    // is conquered or the attacker cannot continue.
+    fn battle_outcomes(
+        &mut self,
+        n_attack_armies: u32,
+        n_defend_armies: u32,
+        max_attack_dice: u32,
+        max_defend_dice: u32,
+        defence_bonus: u32) -> Vec<(BattleOutcome, f64)> {
+        let key = (n_attack_armies, n_defend_armies, max_attack_dice, max_defend_dice, defence_bonus);
+        if let Some(outcomes) = self.battles.get(&key) {
+            return outcomes.clone();
+        }
+
+        let mut chances: HashMap<BattleOutcome, f64> = HashMap::new();
+        if n_attack_armies <= self.rules.armies_left_behind {
+            chances.insert(BattleOutcome::Repelled { defenders_left: n_defend_armies }, 1.0);
+        } else {
+            let n_attacking_dice = std::cmp::min(n_attack_armies - self.rules.armies_left_behind, max_attack_dice);
+            let n_defending_dice = std::cmp::min(n_defend_armies, max_defend_dice);
+            for ((attacker_losses, defender_losses), round_chance) in self.round_outcomes(n_attacking_dice, n_defending_dice, defence_bonus) {
+                let attackers_left = n_attack_armies - attacker_losses;
+                let defenders_left = n_defend_armies - defender_losses;
+                if defenders_left == 0 {
+                    let outcome = BattleOutcome::Conquered { attackers_left, min_move: n_attacking_dice };
+                    *chances.entry(outcome).or_default() += round_chance;
+                    continue;
+                }
+                for (outcome, chance) in self.battle_outcomes(attackers_left, defenders_left, max_attack_dice, max_defend_dice, defence_bonus) {
+                    *chances.entry(outcome).or_default() += round_chance * chance;
+                }
+            }
+        }
+
+        let mut outcomes: Vec<(BattleOutcome, f64)> = chances.into_iter().collect();
+        outcomes.sort_by_key(|(outcome, _)| match outcome {
+            BattleOutcome::Conquered { attackers_left, .. } => (0, *attackers_left),
+            BattleOutcome::Repelled { defenders_left } => (1, *defenders_left),
+        });
+        self.battles.insert(key, outcomes.clone());
+        outcomes
+    }
+
+    // Chances of the (attacker, defender) losses of a single round, found by
+    // going through every roll of the dice. As in combat, the defence bonus
+    // raises the highest defending die and ties go to the defender.
+    fn round_outcomes(&mut self, n_attacking_dice: u32, n_defending_dice: u32, defence_bonus: u32) -> Vec<(Losses, f64)> {
+        let key = (n_attacking_dice, n_defending_dice, defence_bonus);
+        if let Some(outcomes) = self.rounds.get(&key) {
+            return outcomes.clone();
+        }
+
+        let n_dice = n_attacking_dice + n_defending_dice;
+        let n_rolls = 6u32.pow(n_dice);
+        let mut counts: HashMap<Losses, u32> = HashMap::new();
+        for roll_index in 0..n_rolls {
+            let rolls: Vec<u8> = (0..n_dice).map(|die| (roll_index / 6u32.pow(die) % 6 + 1) as u8).collect();
+            let (attacking_rolls, defending_rolls) = rolls.split_at(n_attacking_dice as usize);
+            let mut attacking_rolls = attacking_rolls.to_vec();
+            let mut defending_rolls = defending_rolls.to_vec();
+            attacking_rolls.sort_by(|a, b| b.cmp(a));
+            defending_rolls.sort_by(|a, b| b.cmp(a));
+            defending_rolls[0] = raise_roll(defending_rolls[0], defence_bonus);
+
+            let mut losses = (0, 0);
+            for (attacking_roll, defending_roll) in attacking_rolls.iter().zip(defending_rolls.iter()) {
+                if attacking_roll > defending_roll {
+                    losses.1 += 1;
+                } else {
+                    losses.0 += 1;
+                }
+            }
+            *counts.entry(losses).or_default() += 1;
+        }
+
+        let mut outcomes: Vec<(Losses, f64)> = counts
+            .into_iter()
+            .map(|(losses, count)| (losses, count as f64 / n_rolls as f64))
+            .collect();
+        outcomes.sort_by_key(|(losses, _)| *losses);
+        self.rounds.insert(key, outcomes.clone());
+        outcomes
+    }
+
+    // The best line of attacks, following the most likely conquest after each
+    // attack, e.g. "Attack Indonesia (3) from Western Australia (0), moving in 4 armies".
+    fn best_line(&mut self, players: &[Player]) -> Vec<String> {
+        let mut line = Vec::new();
+        let mut position = players.to_vec();
+        while !self.is_solved(&position) {
+            let mut best_attack = None;
+            let mut best_chance = 0.0;
+            for (from, to, defender_idx) in possible_attacks(self.territories, &position, self.rules) {
+                let chance = self.attack_chance(&position, from, to, defender_idx);
+                if chance > best_chance {
+                    best_attack = Some((from, to, defender_idx));
+                    best_chance = chance;
+                }
+            }
+            let Some((from, to, defender_idx)) = best_attack else {
+                break;
+            };
+
+            let conquest = self.positions_after(&position, from, to, defender_idx)
+                .into_iter()
+                .filter(|(positions, _)| positions[0][0].army_per_territory.contains_key(&to))
+                .max_by(|(_, chance), (_, other_chance)| chance.total_cmp(other_chance));
+            let Some((positions, _)) = conquest else {
+                break;
+            };
+            let mut next_position = positions[0].clone();
+            for other_position in &positions[1..] {
+                if self.success_chance(other_position) > self.success_chance(&next_position) {
+                    next_position = other_position.clone();
+                }
+            }
+
+            line.push(format!("Attack {} ({}) from {} ({}), moving in {} armies",
+                territory_name(self.territories, to),
+                to,
+                territory_name(self.territories, from),
+                from,
+                next_position[0].army_per_territory[&to]));
+            position = next_position;
+        }
+        line
+    }
+}
+
+// Who holds each territory with how many armies, which is all that changes
+// during the attack phase.
+fn position_key(players: &[Player]) -> Vec<(u32, usize, u32)> {
+    let mut key: Vec<(u32, usize, u32)> = players
+        .iter()
+        .enumerate()
+        .flat_map(|(player_idx, player)| player.army_per_territory.iter().map(move |(territory_index, armies)| (*territory_index, player_idx, *armies)))
+        .collect();
+    key.sort();
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::LoadedDice;
+
+    // Ann holds every territory with one army, except for the ones given, and
+    // has to take Australia.
+    fn puzzle_text(map: &Map, holdings: &[(usize, &str, u32)]) -> String {
+        let mut contents = String::from("player Ann\nplayer Bo\nvictory Ann, continents Australia\n");
+        for node_index in map.territories.node_indices() {
+            let (owner, armies) = holdings
+                .iter()
+                .find(|(territory_index, _, _)| *territory_index == node_index.index())
+                .map_or(("Ann", 1), |(_, owner, armies)| (*owner, *armies));
+            contents.push_str(&format!("hold {}, {}, {}\n", map.territories[node_index].name, owner, armies));
+        }
+        contents
+    }
+
+    fn best_line(holdings: &[(usize, &str, u32)]) -> (f64, Vec<String>) {
+        let map = Map::default_map();
+        let rules = RuleSet::classic();
+        let puzzle = Puzzle::parse(&puzzle_text(&map, holdings), &map, &rules).unwrap();
+        let mut solver = Solver::new(&map.territories, &map.continents, &rules, puzzle.goal());
+        (solver.success_chance(&puzzle.scenario.players), solver.best_line(&puzzle.scenario.players))
+    }
+
+    #[test]
+    fn puzzles_read_their_dice_and_goal() {
+        let map = Map::default_map();
+        let rules = RuleSet::classic();
+        let contents = format!("{}dice 6 1\n", puzzle_text(&map, &[(3, "Bo", 1)]));
+        let puzzle = Puzzle::parse(&contents, &map, &rules).unwrap();
+        assert_eq!(puzzle.dice, Some(vec![6, 1]));
+        assert!(matches!(puzzle.goal(), Mission::HoldContinents(_)));
+
+        let error = |contents: &str| Puzzle::parse(contents, &map, &rules).err().unwrap();
+        assert_eq!(error("player Ann\nhold Atlantis, Ann, 1\ndice 7"), "line 3: expected 'dice' followed by rolls from 1 to 6");
+        let contents = puzzle_text(&map, &[(3, "Bo", 1)]).replace("victory Ann, continents Australia\n", "");
+        assert_eq!(error(&contents), "the puzzle needs a victory condition for Ann, who moves first");
+    }
+
+    #[test]
+    fn solver_odds_match_the_dice() {
+        // A single die against a single die wins 15 times out of 36.
+        let (chance, line) = best_line(&[(0, "Ann", 2), (3, "Bo", 1)]);
+        assert!((chance - 15.0 / 36.0).abs() < 1e-9);
+        assert_eq!(line, vec!["Attack Indonesia (3) from Western Australia (0), moving in 1 armies"]);
+
+        // With more armies the attack can go on after losing a round.
+        let (chance, _) = best_line(&[(0, "Ann", 3), (3, "Bo", 1)]);
+        assert!(chance > 15.0 / 36.0 && chance < 1.0);
+
+        // Nothing can attack, so the puzzle cannot be solved.
+        let (chance, line) = best_line(&[(3, "Bo", 1)]);
+        assert_eq!(chance, 0.0);
+        assert!(line.is_empty());
+    }
+
+    #[test]
+    fn best_line_moves_in_as_many_armies_as_the_next_attack_needs() {
+        // Eastern Australia (1) has to attack both Western Australia (0) and
+        // New Guinea (2), so it moves in as few armies as it can. The last
+        // conquest solves the puzzle either way, which keeps the fewest.
+        let (_, line) = best_line(&[(1, "Ann", 10), (0, "Bo", 1), (2, "Bo", 1)]);
+        assert_eq!(line, vec![
+            "Attack Western Australia (0) from Eastern Australia (1), moving in 3 armies",
+            "Attack New Guinea (2) from Eastern Australia (1), moving in 3 armies",
+        ]);
+
+        // Indonesia (3) can only be reached through New Guinea (2), so
+        // everything moves on.
+        let (_, line) = best_line(&[(1, "Ann", 10), (2, "Bo", 1), (3, "Bo", 1)]);
+        assert_eq!(line, vec![
+            "Attack New Guinea (2) from Eastern Australia (1), moving in 9 armies",
+            "Attack Indonesia (3) from New Guinea (2), moving in 3 armies",
+        ]);
+    }
+
+    #[test]
+    fn puzzles_are_attempted_with_the_given_dice() {
+        let map = Map::default_map();
+        let rules = RuleSet::classic();
+        let puzzle = Puzzle::parse(&puzzle_text(&map, &[(0, "Ann", 3), (3, "Bo", 1)]), &map, &rules).unwrap();
+
+        let mut input = Input::from_lines(&["y", "0", "3"]);
+        assert!(play(&puzzle, &map, &rules, &mut input, &mut LoadedDice::new(&[6, 6, 1])));
+
+        // The defender wins the only round, and no attack is left.
+        let mut input = Input::from_lines(&["y", "0", "3"]);
+        assert!(!play(&puzzle, &map, &rules, &mut input, &mut LoadedDice::new(&[1, 1, 6])));
+    }
+}
@@ -0,0 +1,478 @@
+// Rule variants that every phase of the game reads from, so that house rules
+// can be switched via a preset name or a config file without recompiling.
+
+use std::fs;
+
+use crate::{BorderKind, Terrain, Territory, MAX_PLAYERS};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameMode {
+    // The game is won by conquering every territory.
+    Domination,
+    // Each player is dealt a secret mission, and the first to complete it wins.
+    Missions,
+    // Each player chooses a capital, and the game is won by holding enough capitals.
+    Capitals,
+}
+
+// Order in which score ties are broken, after which turn order decides.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TieBreak {
+    Territories,
+    Armies,
+    Continents,
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleSet {
+    pub name: String,
+    // Starting armies per player, indexed by number of players minus one.
+    pub starting_armies: Vec<u32>,
+    // Number of territories the starting_armies table is meant for. On bigger or
+    // smaller maps the starting armies are scaled in proportion.
+    pub starting_armies_map_size: u32,
+    // Reinforcements are max(min_reinforcements, territories / territories_per_reinforcement).
+    pub min_reinforcements: u32,
+    pub territories_per_reinforcement: u32,
+    pub max_attack_dice: u32,
+    pub max_defend_dice: u32,
+    // Number of armies that must stay behind when attacking or moving out of a territory.
+    pub armies_left_behind: u32,
+    // Attacks across a sea border roll this many fewer dice, but at least one.
+    pub sea_attack_dice_penalty: u32,
+    // Whether fortify moves may cross sea borders, or only land borders.
+    pub fortify_over_sea: bool,
+    // The defender's highest die counts this much more on mountains or urban
+    // territories, and a fortress lets them roll up to fortress_defend_dice dice.
+    pub mountain_defence_bonus: u32,
+    pub urban_defence_bonus: u32,
+    pub fortress_defend_dice: u32,
+    // Reinforcements count the production of the territories held instead of
+    // their number.
+    pub reinforce_by_production: bool,
+    // Players earn gold every turn and spend it on armies and buildings.
+    pub economy: bool,
+    pub gold_per_territory: u32,
+    pub gold_per_continent_bonus: u32, // Per bonus point of each continent held in full
+    pub army_cost: u32,
+    pub city_cost: u32,
+    pub fortification_cost: u32,
+    // Each city adds reinforcements, and makes the defender's highest die count
+    // more when it is attacked.
+    pub city_reinforcements: u32,
+    pub city_defence_bonus: u32,
+    // How much a bunker left by a campaign winner makes the defender's highest
+    // die count more.
+    pub bunker_defence_bonus: u32,
+    pub fog_of_war: bool,
+    // Adds a neutral player to two-player games, as in classic two-player Risk.
+    pub neutral_in_two_player_games: bool,
+    pub game_mode: GameMode,
+    // Mission deck to deal from in mission mode, the built-in deck is used if unset.
+    pub missions_file: Option<String>,
+    // Capitals needed to win in capital conquest mode, indexed by number of
+    // players minus one. A player's own capital counts.
+    pub capitals_to_win: Vec<u32>,
+    // Optional limits after which the game is decided on score. A turn here is
+    // one round in which every player has taken a turn.
+    pub max_turns: Option<u32>,
+    pub time_limit_minutes: Option<u32>,
+    pub score_per_territory: u32,
+    pub score_per_army: u32,
+    pub score_per_continent_bonus: u32,
+    pub tie_break: Vec<TieBreak>,
+    // Lets players propose pacts and territory swaps at the start of their turn.
+    pub diplomacy: bool,
+}
+
+impl RuleSet {
+    pub fn classic() -> Self {
+        RuleSet {
+            name: "classic".to_string(),
+            starting_armies: vec![45, 40, 35, 30, 25, 20, 18, 16],
+            starting_armies_map_size: 16,
+            min_reinforcements: 3,
+            territories_per_reinforcement: 3,
+            max_attack_dice: 3,
+            max_defend_dice: 2,
+            armies_left_behind: 1,
+            sea_attack_dice_penalty: 0,
+            fortify_over_sea: true,
+            mountain_defence_bonus: 1,
+            urban_defence_bonus: 0,
+            fortress_defend_dice: 3,
+            reinforce_by_production: false,
+            economy: false,
+            gold_per_territory: 1,
+            gold_per_continent_bonus: 1,
+            army_cost: 3,
+            city_cost: 12,
+            fortification_cost: 8,
+            city_reinforcements: 1,
+            city_defence_bonus: 1,
+            bunker_defence_bonus: 1,
+            fog_of_war: false,
+            neutral_in_two_player_games: true,
+            game_mode: GameMode::Domination,
+            missions_file: None,
+            capitals_to_win: vec![1, 2, 3, 3, 4, 4, 5, 5],
+            max_turns: None,
+            time_limit_minutes: None,
+            score_per_territory: 3,
+            score_per_army: 1,
+            score_per_continent_bonus: 3,
+            tie_break: vec![TieBreak::Territories, TieBreak::Armies, TieBreak::Continents],
+            diplomacy: false,
+        }
+    }
+
+    // Our house variant: fewer starting armies, but bigger reinforcements each
+    // turn, and the board is hidden by fog of war.
+    pub fn house() -> Self {
+        RuleSet {
+            name: "house".to_string(),
+            starting_armies: vec![40, 35, 30, 25, 20, 15, 13, 11],
+            min_reinforcements: 4,
+            territories_per_reinforcement: 2,
+            fog_of_war: true,
+            ..RuleSet::classic()
+        }
+    }
+
+    // Loads a rule set from either a preset name ("classic" or "house") or the
+    // path of a config file, and validates it.
+    pub fn load(source: &str) -> Result<RuleSet, String> {
+        let rules = match source {
+            "classic" => RuleSet::classic(),
+            "house" => RuleSet::house(),
+            path => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| format!("could not read rules file '{}': {}", path, e))?;
+                RuleSet::parse(path, &contents)?
+            }
+        };
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    // Parses a config file made of "key = value" lines. Blank lines and lines
+    // starting with '#' are ignored. An optional "preset" key selects the rule set
+    // the remaining keys override, and defaults to classic.
+    pub fn parse(name: &str, contents: &str) -> Result<RuleSet, String> {
+        let mut rules = RuleSet::classic();
+        rules.name = name.to_string();
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'key = value', found '{}'", line_number, line))?;
+            let key = key.trim();
+            let value = value.trim();
+
+            match key {
+                "preset" => {
+                    rules = match value {
+                        "classic" => RuleSet::classic(),
+                        "house" => RuleSet::house(),
+                        _ => return Err(format!("line {}: unknown preset '{}', expected 'classic' or 'house'", line_number, value)),
+                    };
+                    rules.name = name.to_string();
+                }
+                "starting_armies" => {
+                    rules.starting_armies = value
+                        .split(',')
+                        .map(|army_count| parse_number(line_number, key, army_count.trim()))
+                        .collect::<Result<Vec<u32>, String>>()?;
+                }
+                "starting_armies_map_size" => rules.starting_armies_map_size = parse_number(line_number, key, value)?,
+                "min_reinforcements" => rules.min_reinforcements = parse_number(line_number, key, value)?,
+                "territories_per_reinforcement" => rules.territories_per_reinforcement = parse_number(line_number, key, value)?,
+                "max_attack_dice" => rules.max_attack_dice = parse_number(line_number, key, value)?,
+                "max_defend_dice" => rules.max_defend_dice = parse_number(line_number, key, value)?,
+                "armies_left_behind" => rules.armies_left_behind = parse_number(line_number, key, value)?,
+                "sea_attack_dice_penalty" => rules.sea_attack_dice_penalty = parse_number(line_number, key, value)?,
+                "fortify_over_sea" => rules.fortify_over_sea = parse_bool(line_number, key, value)?,
+                "mountain_defence_bonus" => rules.mountain_defence_bonus = parse_number(line_number, key, value)?,
+                "urban_defence_bonus" => rules.urban_defence_bonus = parse_number(line_number, key, value)?,
+                "fortress_defend_dice" => rules.fortress_defend_dice = parse_number(line_number, key, value)?,
+                "reinforce_by_production" => rules.reinforce_by_production = parse_bool(line_number, key, value)?,
+                "economy" => rules.economy = parse_bool(line_number, key, value)?,
+                "gold_per_territory" => rules.gold_per_territory = parse_number(line_number, key, value)?,
+                "gold_per_continent_bonus" => rules.gold_per_continent_bonus = parse_number(line_number, key, value)?,
+                "army_cost" => rules.army_cost = parse_number(line_number, key, value)?,
+                "city_cost" => rules.city_cost = parse_number(line_number, key, value)?,
+                "fortification_cost" => rules.fortification_cost = parse_number(line_number, key, value)?,
+                "city_reinforcements" => rules.city_reinforcements = parse_number(line_number, key, value)?,
+                "city_defence_bonus" => rules.city_defence_bonus = parse_number(line_number, key, value)?,
+                "bunker_defence_bonus" => rules.bunker_defence_bonus = parse_number(line_number, key, value)?,
+                "fog_of_war" => rules.fog_of_war = parse_bool(line_number, key, value)?,
+                "neutral_in_two_player_games" => rules.neutral_in_two_player_games = parse_bool(line_number, key, value)?,
+                "game_mode" => {
+                    rules.game_mode = match value {
+                        "domination" => GameMode::Domination,
+                        "missions" => GameMode::Missions,
+                        "capitals" => GameMode::Capitals,
+                        _ => return Err(format!("line {}: unknown game mode '{}', expected 'domination', 'missions' or 'capitals'", line_number, value)),
+                    };
+                }
+                "missions_file" => rules.missions_file = Some(value.to_string()),
+                "capitals_to_win" => {
+                    rules.capitals_to_win = value
+                        .split(',')
+                        .map(|capital_count| parse_number(line_number, key, capital_count.trim()))
+                        .collect::<Result<Vec<u32>, String>>()?;
+                }
+                "diplomacy" => rules.diplomacy = parse_bool(line_number, key, value)?,
+                "max_turns" => rules.max_turns = parse_limit(line_number, key, value)?,
+                "time_limit_minutes" => rules.time_limit_minutes = parse_limit(line_number, key, value)?,
+                "score_per_territory" => rules.score_per_territory = parse_number(line_number, key, value)?,
+                "score_per_army" => rules.score_per_army = parse_number(line_number, key, value)?,
+                "score_per_continent_bonus" => rules.score_per_continent_bonus = parse_number(line_number, key, value)?,
+                "tie_break" => {
+                    rules.tie_break = value
+                        .split(',')
+                        .map(|tie_break| match tie_break.trim() {
+                            "territories" => Ok(TieBreak::Territories),
+                            "armies" => Ok(TieBreak::Armies),
+                            "continents" => Ok(TieBreak::Continents),
+                            other => Err(format!(
+                                "line {}: unknown tie break '{}', expected territories, armies or continents",
+                                line_number, other)),
+                        })
+                        .collect::<Result<Vec<TieBreak>, String>>()?;
+                }
+                _ => return Err(format!("line {}: unknown rule '{}'", line_number, key)),
+            }
+        }
+
+        Ok(rules)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.starting_armies.len() < MAX_PLAYERS {
+            return Err(format!(
+                "starting_armies must list a value for each player count from 1 to {}, found {} value(s)",
+                MAX_PLAYERS,
+                self.starting_armies.len()));
+        }
+        if self.starting_armies.contains(&0) {
+            return Err("starting_armies values must be at least 1".to_string());
+        }
+        if self.starting_armies_map_size == 0 {
+            return Err("starting_armies_map_size must be at least 1".to_string());
+        }
+        if self.territories_per_reinforcement == 0 {
+            return Err("territories_per_reinforcement must be at least 1".to_string());
+        }
+        if self.max_attack_dice == 0 {
+            return Err("max_attack_dice must be at least 1".to_string());
+        }
+        if self.max_defend_dice == 0 {
+            return Err("max_defend_dice must be at least 1".to_string());
+        }
+        if self.army_cost == 0 {
+            return Err("army_cost must be at least 1".to_string());
+        }
+        // A fortress must never make the defender weaker.
+        if self.fortress_defend_dice < self.max_defend_dice {
+            return Err(format!(
+                "fortress_defend_dice must be at least max_defend_dice ({}), found {}",
+                self.max_defend_dice, self.fortress_defend_dice));
+        }
+        if self.armies_left_behind == 0 {
+            return Err("armies_left_behind must be at least 1, a territory can never be left empty".to_string());
+        }
+        if self.capitals_to_win.len() < MAX_PLAYERS {
+            return Err(format!(
+                "capitals_to_win must list a value for each player count from 1 to {}, found {} value(s)",
+                MAX_PLAYERS,
+                self.capitals_to_win.len()));
+        }
+        for (player_count_index, capital_count) in self.capitals_to_win.iter().enumerate() {
+            let number_of_players = player_count_index as u32 + 1;
+            if *capital_count == 0 || *capital_count > number_of_players {
+                return Err(format!(
+                    "capitals_to_win for {} player(s) must be between 1 and {}, found {}",
+                    number_of_players, number_of_players, capital_count));
+            }
+        }
+        for (i, tie_break) in self.tie_break.iter().enumerate() {
+            if self.tie_break[..i].contains(tie_break) {
+                return Err(format!("tie_break lists {:?} more than once", tie_break));
+            }
+        }
+        Ok(())
+    }
+
+    // Scales the table value to the size of the map, rounding to the nearest
+    // army, but always gives enough armies for one on each territory a player
+    // can be dealt.
+    pub fn starting_armies_for(&self, number_of_players: usize, number_of_territories: usize) -> u32 {
+        let number_of_territories = number_of_territories as u32;
+        let scaled_armies = (self.starting_armies[number_of_players - 1] * number_of_territories
+            + self.starting_armies_map_size / 2)
+            / self.starting_armies_map_size;
+        std::cmp::max(scaled_armies, number_of_territories.div_ceil(number_of_players as u32))
+    }
+
+    // Attacks across the sea roll fewer dice, but always at least one.
+    pub fn max_attack_dice_across(&self, border_kind: BorderKind) -> u32 {
+        match border_kind {
+            BorderKind::Land => self.max_attack_dice,
+            BorderKind::Sea => self.max_attack_dice.saturating_sub(self.sea_attack_dice_penalty).max(1),
+        }
+    }
+
+    pub fn max_defend_dice_with(&self, is_fortified: bool) -> u32 {
+        if is_fortified {
+            self.fortress_defend_dice
+        } else {
+            self.max_defend_dice
+        }
+    }
+
+    // How much the defender's highest die counts for more on the terrain.
+    pub fn defence_bonus_on(&self, terrain: Terrain) -> u32 {
+        match terrain {
+            Terrain::Plains => 0,
+            Terrain::Mountains => self.mountain_defence_bonus,
+            Terrain::Urban => self.urban_defence_bonus,
+        }
+    }
+
+    pub fn capitals_to_win_for(&self, number_of_players: usize) -> u32 {
+        self.capitals_to_win[number_of_players - 1]
+    }
+
+    // Reinforcements for holding the given territories, counted either by number
+    // or by production.
+    pub fn reinforcements_for(&self, territories: &[Territory]) -> u32 {
+        let count = if self.reinforce_by_production {
+            territories.iter().map(|territory| territory.production).sum()
+        } else {
+            territories.len() as u32
+        };
+        std::cmp::max(self.min_reinforcements, count / self.territories_per_reinforcement)
+    }
+
+    pub fn print_summary(&self) {
+        println!("Playing with the {} rules:", self.name);
+        println!("  Starting armies by number of players: {:?}, for a map of {} territories",
+            self.starting_armies,
+            self.starting_armies_map_size);
+        println!("  Reinforcements: max({}, {} / {})",
+            self.min_reinforcements,
+            if self.reinforce_by_production { "production" } else { "territories" },
+            self.territories_per_reinforcement);
+        println!("  Dice: up to {} to attack, up to {} to defend",
+            self.max_attack_dice,
+            self.max_defend_dice);
+        println!("  Armies left behind when attacking: {}", self.armies_left_behind);
+        if self.sea_attack_dice_penalty > 0 {
+            println!("  Attacks across the sea roll {} fewer dice", self.sea_attack_dice_penalty);
+        }
+        println!("  Defence: highest die +{} in the mountains, +{} in urban territories, up to {} dice in a fortress",
+            self.mountain_defence_bonus,
+            self.urban_defence_bonus,
+            self.fortress_defend_dice);
+        if self.economy {
+            println!("  Economy: {} gold per territory, {} per continent bonus point; armies cost {}, cities {}, fortifications {}",
+                self.gold_per_territory,
+                self.gold_per_continent_bonus,
+                self.army_cost,
+                self.city_cost,
+                self.fortification_cost);
+            println!("  Cities: +{} reinforcements each, defender's highest die +{}",
+                self.city_reinforcements,
+                self.city_defence_bonus);
+        }
+        println!("  Fortify across the sea: {}", if self.fortify_over_sea { "on" } else { "off" });
+        println!("  Fog of war: {}", if self.fog_of_war { "on" } else { "off" });
+        println!("  Neutral player in two-player games: {}", if self.neutral_in_two_player_games { "on" } else { "off" });
+        println!("  Diplomacy: {}", if self.diplomacy { "on" } else { "off" });
+        match self.game_mode {
+            GameMode::Domination => println!("  Game mode: world domination"),
+            GameMode::Missions => println!("  Game mode: secret missions"),
+            GameMode::Capitals => println!("  Game mode: capital conquest, capitals to win by number of players: {:?}", self.capitals_to_win),
+        }
+        if let Some(max_turns) = self.max_turns {
+            println!("  Turn limit: {} turns", max_turns);
+        }
+        if let Some(time_limit_minutes) = self.time_limit_minutes {
+            println!("  Time limit: {} minutes", time_limit_minutes);
+        }
+        if self.max_turns.is_some() || self.time_limit_minutes.is_some() {
+            println!("  Score: {} per territory, {} per army, {} per continent bonus point, ties broken by {:?}",
+                self.score_per_territory,
+                self.score_per_army,
+                self.score_per_continent_bonus,
+                self.tie_break);
+        }
+    }
+}
+
+fn parse_number(line_number: usize, key: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("line {}: '{}' expects a non-negative number, found '{}'", line_number, key, value))
+}
+
+// Limits are given as a number, where 0 or "none" means no limit.
+fn parse_limit(line_number: usize, key: &str, value: &str) -> Result<Option<u32>, String> {
+    if value == "none" {
+        return Ok(None);
+    }
+    let limit = parse_number(line_number, key, value)?;
+    Ok(if limit == 0 { None } else { Some(limit) })
+}
+
+fn parse_bool(line_number: usize, key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("line {}: '{}' expects true or false, found '{}'", line_number, key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starting_armies_scale_with_the_map_size() {
+        let rules = RuleSet::classic();
+        assert_eq!(rules.starting_armies_for(2, 16), 40);
+        assert_eq!(rules.starting_armies_for(2, 42), 105);
+        assert_eq!(rules.starting_armies_for(8, 8), 8);
+        // Every territory a player is dealt needs at least one army.
+        assert_eq!(rules.starting_armies_for(8, 200), 200);
+        assert_eq!(rules.starting_armies_for(1, 4), 11);
+    }
+
+    #[test]
+    fn reinforcements_can_count_production() {
+        let mut territories = vec![Territory::new("A"), Territory::new("B"), Territory::new("C")];
+        territories[0].production = 10;
+        let mut rules = RuleSet::classic();
+        assert_eq!(rules.reinforcements_for(&territories), 3);
+        rules.reinforce_by_production = true;
+        assert_eq!(rules.reinforcements_for(&territories), 4);
+    }
+
+    #[test]
+    fn invalid_rules_files_are_reported() {
+        let error = |contents: &str| RuleSet::parse("test", contents).and_then(|rules| rules.validate()).unwrap_err();
+        assert_eq!(error("max_attack_dice"), "line 1: expected 'key = value', found 'max_attack_dice'");
+        assert_eq!(error("max_attack_dice = many"), "line 1: 'max_attack_dice' expects a non-negative number, found 'many'");
+        assert_eq!(error("max_defend_dice = 0"), "max_defend_dice must be at least 1");
+        assert_eq!(error("fortress_defend_dice = 1"), "fortress_defend_dice must be at least max_defend_dice (2), found 1");
+        assert_eq!(error("max_defend_dice = 3\nfortress_defend_dice = 2"), "fortress_defend_dice must be at least max_defend_dice (3), found 2");
+        assert!(RuleSet::parse("test", "max_defend_dice = 3\nfortress_defend_dice = 3").unwrap().validate().is_ok());
+    }
+}
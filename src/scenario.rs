@@ -0,0 +1,236 @@
+// Scenarios, played with "--scenario <file>", start the game from a fixed
+// position instead of dealing territories and placing armies. A scenario file
+// lists the players in turn order, who holds each territory with how many
+// armies, and optionally who moves first and what each player has to achieve:
+//
+//     description Bo holds out in Siberia against Ann's advance.
+//     player Ann, Red
+//     player Bo, Blue
+//     neutral
+//     hold Siberia, Bo, 8
+//     hold Ural, Ann, 12
+//     ...
+//     first Bo
+//     victory Bo, territories 6 1
+//     victory Ann, continents Asia
+//
+// Victory conditions are written like missions in the mission deck, and a
+// player without one has to conquer the world. Players can also be given a
+// team ("team Ann, 1"), a capital when playing for capitals ("capital Ann,
+// Ural") and gold ("gold Ann, 10"). Blank lines and lines starting with '#' are
+// ignored.
+
+use std::fs;
+
+use petgraph::graph::NodeIndex;
+
+use crate::map::Map;
+use crate::missions::{describe_mission, parse_player_mission};
+use crate::rules::{GameMode, RuleSet};
+use crate::{setup_players, Color, Player, MAX_PLAYERS};
+
+pub struct Scenario {
+    pub description: Vec<String>,
+    // Players in turn order, starting with the one who moves first.
+    pub players: Vec<Player>,
+}
+
+impl Scenario {
+    pub fn load(path: &str, map: &Map, rules: &RuleSet) -> Result<Scenario, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("could not read scenario file '{}': {}", path, e))?;
+        Scenario::parse(&contents, map, rules)
+    }
+
+    pub fn parse(contents: &str, map: &Map, rules: &RuleSet) -> Result<Scenario, String> {
+        let lines: Vec<(usize, &str, &str)> = contents
+            .lines()
+            .enumerate()
+            .map(|(line_index, line)| (line_index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line_number, line)| {
+                let (kind, arguments) = line.split_once(' ').unwrap_or((line, ""));
+                (line_number, kind, arguments.trim())
+            })
+            .collect();
+
+        // Players come first, since the other lines refer to them by name.
+        let mut players_and_colors: Vec<(String, Option<Color>)> = Vec::new();
+        let mut has_neutral = false;
+        for (line_number, kind, arguments) in &lines {
+            match *kind {
+                "player" => {
+                    let (name, color) = match arguments.split_once(',') {
+                        Some((name, color)) => {
+                            let color = Color::from_name(color.trim())
+                                .filter(|color| *color != Color::Grey)
+                                .ok_or_else(|| format!("line {}: unknown colour '{}'", line_number, color.trim()))?;
+                            (name.trim(), Some(color))
+                        }
+                        None => (*arguments, None),
+                    };
+                    if name.is_empty() || name == "Neutral" || players_and_colors.iter().any(|(other, _)| other == name) {
+                        return Err(format!("line {}: '{}' cannot be used as a player name", line_number, name));
+                    }
+                    if let Some(color) = &color {
+                        if players_and_colors.iter().any(|(_, other_color)| other_color.as_ref() == Some(color)) {
+                            return Err(format!("line {}: the colour {} is taken twice", line_number, color.name()));
+                        }
+                    }
+                    players_and_colors.push((name.to_string(), color));
+                }
+                "neutral" => has_neutral = true,
+                _ => {}
+            }
+        }
+        if players_and_colors.is_empty() {
+            return Err("the scenario does not have any players".to_string());
+        }
+        if players_and_colors.len() > MAX_PLAYERS {
+            return Err(format!("the scenario has more than {} players", MAX_PLAYERS));
+        }
+        let mut players = setup_players(players_and_colors);
+        if has_neutral {
+            players.push(Player::neutral());
+        }
+
+        let mut description = Vec::new();
+        let mut first_player_idx = 0;
+        for (line_number, kind, arguments) in &lines {
+            let line_number = *line_number;
+            let fields: Vec<&str> = arguments.splitn(3, ',').map(|field| field.trim()).collect();
+            let player_idx = |name: &str| {
+                players
+                    .iter()
+                    .position(|player| player.name == name)
+                    .ok_or_else(|| format!("line {}: unknown player '{}'", line_number, name))
+            };
+            let territory_index = |name: &str| {
+                map.find_territory(name)
+                    .ok_or_else(|| format!("line {}: unknown territory '{}'", line_number, name))
+            };
+            let number = |value: &str| {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("line {}: expected a number, found '{}'", line_number, value))
+            };
+            let expect_fields = |n_fields: usize, usage: &str| {
+                if fields.len() == n_fields {
+                    Ok(())
+                } else {
+                    Err(format!("line {}: expected '{} {}'", line_number, kind, usage))
+                }
+            };
+
+            match *kind {
+                "player" | "neutral" => {}
+                "description" => description.push(arguments.to_string()),
+                "hold" => {
+                    expect_fields(3, "<territory>, <player>, <armies>")?;
+                    let territory_index = territory_index(fields[0])?;
+                    let player_idx = player_idx(fields[1])?;
+                    let armies = number(fields[2])?;
+                    if armies == 0 {
+                        return Err(format!("line {}: a territory needs at least 1 army", line_number));
+                    }
+                    if players.iter().any(|player| player.army_per_territory.contains_key(&territory_index)) {
+                        return Err(format!("line {}: {} is held twice", line_number, fields[0]));
+                    }
+                    players[player_idx].army_per_territory.insert(territory_index, armies);
+                }
+                "first" => first_player_idx = player_idx(arguments)?,
+                "team" => {
+                    expect_fields(2, "<player>, <team>")?;
+                    let player_idx = player_idx(fields[0])?;
+                    players[player_idx].team = Some(number(fields[1])?);
+                }
+                "capital" => {
+                    if rules.game_mode != GameMode::Capitals {
+                        return Err(format!("line {}: capitals are only used with game_mode = capitals", line_number));
+                    }
+                    expect_fields(2, "<player>, <territory>")?;
+                    let player_idx = player_idx(fields[0])?;
+                    players[player_idx].capital = Some(territory_index(fields[1])?);
+                }
+                "gold" => {
+                    expect_fields(2, "<player>, <gold>")?;
+                    let player_idx = player_idx(fields[0])?;
+                    players[player_idx].gold = number(fields[1])?;
+                }
+                "victory" => {
+                    let (name, mission) = arguments
+                        .split_once(',')
+                        .ok_or_else(|| format!("line {}: expected 'victory <player>, <mission>'", line_number))?;
+                    let player_idx = player_idx(name.trim())?;
+                    players[player_idx].mission = Some(parse_player_mission(line_number, mission.trim(), &map.territories, &map.continents)?);
+                }
+                _ => return Err(format!(
+                    "line {}: unknown line '{}', expected description, player, neutral, hold, first, team, capital, gold or victory",
+                    line_number, kind)),
+            }
+        }
+
+        for node_index in map.territories.node_indices() {
+            let territory_index = node_index.index() as u32;
+            if !players.iter().any(|player| player.army_per_territory.contains_key(&territory_index)) {
+                return Err(format!("{} is not held by anyone", map.territories[NodeIndex::new(territory_index as usize)].name));
+            }
+        }
+        if players[first_player_idx].is_neutral {
+            return Err("the neutral player cannot move first".to_string());
+        }
+
+        // The player who moves first starts each round, keeping the turn order.
+        players.rotate_left(first_player_idx);
+        Ok(Scenario { description, players })
+    }
+
+    pub fn print_summary(&self, map: &Map) {
+        println!("==== Scenario ====");
+        for line in &self.description {
+            println!("{}", line);
+        }
+        for player in &self.players {
+            if let Some(mission) = &player.mission {
+                println!("{} wins by completing: {}.", player.name, describe_mission(mission, &map.continents));
+            }
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenarios_fix_the_starting_position() {
+        let mut contents = String::from("description A test.\nplayer Ann, Red\nplayer Bo\nneutral\nfirst Bo\nvictory Ann, continents Australia\nteam Bo, 2\n");
+        let map = Map::default_map();
+        for node_index in map.territories.node_indices() {
+            let owner = match node_index.index() {
+                0..=2 => "Ann",
+                3 => "Neutral",
+                _ => "Bo",
+            };
+            contents.push_str(&format!("hold {}, {}, {}\n", map.territories[node_index].name, owner, node_index.index() + 1));
+        }
+        let scenario = Scenario::parse(&contents, &map, &RuleSet::classic()).unwrap();
+
+        let names: Vec<&str> = scenario.players.iter().map(|player| player.name.as_str()).collect();
+        assert_eq!(names, vec!["Bo", "Neutral", "Ann"]);
+        assert_eq!(scenario.players[0].color, Color::Blue);
+        assert_eq!(scenario.players[0].team, Some(2));
+        assert_eq!(scenario.players[1].army_per_territory[&3], 4);
+        assert_eq!(scenario.players[2].army_per_territory.len(), 3);
+        assert!(scenario.players[2].mission.is_some());
+
+        let error = |contents: &str| Scenario::parse(contents, &map, &RuleSet::classic()).err().unwrap();
+        assert_eq!(error("player Ann\nhold Atlantis, Ann, 3"), "line 2: unknown territory 'Atlantis'");
+        assert_eq!(error("player Ann\nhold India, Bo, 3"), "line 2: unknown player 'Bo'");
+        assert_eq!(error("player Ann\nhold India, Ann, 3"), "Western Australia is not held by anyone");
+        let nine_players: String = (1..=9).map(|n| format!("player P{}\n", n)).collect();
+        assert_eq!(error(&nine_players), "the scenario has more than 8 players");
+        assert_eq!(error("player Ann\ncapital Ann, India"), "line 2: capitals are only used with game_mode = capitals");
+    }
+}
@@ -0,0 +1,215 @@
+// Score-based standings, used to pick a winner when a game hits its turn or time
+// limit, and to print the final standings at the end of every game.
+
+use std::cmp::Ordering;
+
+use crate::rules::{RuleSet, TieBreak};
+use crate::{teams, Continent, Player};
+
+pub struct PlayerScore {
+    pub player_idx: usize,
+    pub territories: u32,
+    pub armies: u32,
+    // Sum of the bonuses of all continents held in full.
+    pub continent_bonus: u32,
+    pub score: u32,
+}
+
+impl PlayerScore {
+    fn tie_break_value(&self, tie_break: &TieBreak) -> u32 {
+        match tie_break {
+            TieBreak::Territories => self.territories,
+            TieBreak::Armies => self.armies,
+            TieBreak::Continents => self.continent_bonus,
+        }
+    }
+}
+
+// Sum of the bonuses of the continents the player holds in full.
+pub fn held_continent_bonus(player: &Player, continents: &[Continent]) -> u32 {
+    continents
+        .iter()
+        .filter(|continent| continent
+            .territories
+            .iter()
+            .all(|territory_index| player.army_per_territory.contains_key(territory_index)))
+        .map(|continent| continent.bonus)
+        .sum()
+}
+
+fn score_player(player_idx: usize, players: &[Player], continents: &[Continent], rules: &RuleSet) -> PlayerScore {
+    let player = &players[player_idx];
+    let territories = player.army_per_territory.len() as u32;
+    let armies = player.total_armies();
+    let continent_bonus = held_continent_bonus(player, continents);
+
+    PlayerScore {
+        player_idx,
+        territories,
+        armies,
+        continent_bonus,
+        score: territories * rules.score_per_territory
+            + armies * rules.score_per_army
+            + continent_bonus * rules.score_per_continent_bonus,
+    }
+}
+
+// Ranks every non-neutral player by score. Ties are broken by the rules' tie
+// break order, and finally by turn order.
+pub fn compute_standings(players: &[Player], continents: &[Continent], rules: &RuleSet) -> Vec<PlayerScore> {
+    let mut standings: Vec<PlayerScore> = (0..players.len())
+        .filter(|player_idx| !players[*player_idx].is_neutral)
+        .map(|player_idx| score_player(player_idx, players, continents, rules))
+        .collect();
+
+    standings.sort_by(|a, b| rank(a, b, rules));
+    standings
+}
+
+// Orders scores from best to worst: by score, then by the rules' tie break
+// order, and finally by turn order.
+fn rank(a: &PlayerScore, b: &PlayerScore, rules: &RuleSet) -> Ordering {
+    let mut ordering = b.score.cmp(&a.score);
+    for tie_break in rules.tie_break.iter() {
+        ordering = ordering.then(b.tie_break_value(tie_break).cmp(&a.tie_break_value(tie_break)));
+    }
+    ordering.then(a.player_idx.cmp(&b.player_idx))
+}
+
+// Picks the side with the highest combined score, adding up the scores,
+// territories, armies and continent bonuses of teammates, and breaking ties like
+// the standings do. Returns the index of that side's best-placed player and the
+// side's score.
+pub fn winning_side(players: &[Player], continents: &[Continent], rules: &RuleSet) -> (usize, u32) {
+    let mut sides: Vec<PlayerScore> = Vec::new();
+    for player_score in compute_standings(players, continents, rules) {
+        match sides.iter_mut().find(|side| teams::are_allies(players, side.player_idx, player_score.player_idx)) {
+            Some(side) => {
+                side.territories += player_score.territories;
+                side.armies += player_score.armies;
+                side.continent_bonus += player_score.continent_bonus;
+                side.score += player_score.score;
+            }
+            None => sides.push(player_score),
+        }
+    }
+    sides.sort_by(|a, b| rank(a, b, rules));
+    (sides[0].player_idx, sides[0].score)
+}
+
+pub fn print_standings(players: &[Player], continents: &[Continent], rules: &RuleSet) {
+    println!("\n==== Final standings ====");
+    println!("{:<6}{:<20}{:>8}{:>14}{:>9}{:>18}", "Rank", "Player", "Score", "Territories", "Armies", "Continent bonus");
+    for (rank, player_score) in compute_standings(players, continents, rules).iter().enumerate() {
+        println!("{:<6}{:<20}{:>8}{:>14}{:>9}{:>18}",
+            rank + 1,
+            players[player_score.player_idx].name,
+            player_score.score,
+            player_score.territories,
+            player_score.armies,
+            player_score.continent_bonus);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_continents, setup_territories, Color};
+
+    #[test]
+    fn standings_use_score_then_tie_break_order() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let rules = RuleSet::classic();
+
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+        ];
+        // Ann holds Australia, worth its continent bonus.
+        for territory_index in continents[0].territories.iter() {
+            players[0].army_per_territory.insert(*territory_index, 1);
+        }
+        // Bo and Cy have the same score, but Bo has more territories.
+        for territory_index in 4..9 {
+            players[1].army_per_territory.insert(territory_index, 1);
+        }
+        players[2].army_per_territory.insert(9, 3);
+        players[2].army_per_territory.insert(10, 4);
+        players[2].army_per_territory.insert(11, 4);
+
+        let standings = compute_standings(&players, &continents, &rules);
+        let ranking: Vec<usize> = standings.iter().map(|player_score| player_score.player_idx).collect();
+        assert_eq!(standings[0].continent_bonus, 2);
+        assert_eq!(standings[1].score, standings[2].score);
+        assert_eq!(ranking, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn teams_win_on_their_combined_score() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let rules = RuleSet::classic();
+
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+        ];
+        // Ann has the best score alone, but Bo and Cy together score more.
+        for territory_index in 0..6 {
+            players[0].army_per_territory.insert(territory_index, 1);
+        }
+        for territory_index in 6..10 {
+            players[1].army_per_territory.insert(territory_index, 1);
+        }
+        for territory_index in 10..14 {
+            players[2].army_per_territory.insert(territory_index, 1);
+        }
+        assert_eq!(winning_side(&players, &continents, &rules).0, 0);
+
+        players[1].team = Some(1);
+        players[2].team = Some(1);
+        players[0].team = Some(2);
+        let (winner_idx, score) = winning_side(&players, &continents, &rules);
+        assert!(teams::are_allies(&players, winner_idx, 1));
+        assert_eq!(score, compute_standings(&players, &continents, &rules)[1..].iter().map(|player_score| player_score.score).sum::<u32>());
+    }
+
+    #[test]
+    fn tied_teams_are_split_by_the_tie_break_order() {
+        let territories = setup_territories();
+        let continents = setup_continents(&territories);
+        let mut rules = RuleSet {
+            score_per_territory: 1,
+            score_per_army: 1,
+            score_per_continent_bonus: 0,
+            ..RuleSet::classic()
+        };
+
+        let mut players = vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+            Player::new("Di".to_string(), Color::Yellow),
+        ];
+        for (player, team) in players.iter_mut().zip([1, 2, 1, 2]) {
+            player.team = Some(team);
+        }
+        // Both teams score 8: Ann and Cy with fewer territories but more armies.
+        players[0].army_per_territory.insert(4, 3);
+        players[2].army_per_territory.insert(5, 3);
+        for territory_index in 6..8 {
+            players[1].army_per_territory.insert(territory_index, 1);
+        }
+        for territory_index in 8..10 {
+            players[3].army_per_territory.insert(territory_index, 1);
+        }
+
+        rules.tie_break = vec![TieBreak::Territories];
+        assert_eq!(winning_side(&players, &continents, &rules), (1, 8));
+        rules.tie_break = vec![TieBreak::Armies];
+        assert_eq!(winning_side(&players, &continents, &rules), (0, 8));
+    }
+}
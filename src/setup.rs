@@ -0,0 +1,529 @@
+// Setup phase: dealing or drafting territories, and placing starting armies so
+// that every player ends up with exactly their quota of armies on the board.
+
+use petgraph::graph::UnGraph;
+use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
+
+use crate::input::Input;
+use crate::rules::RuleSet;
+use crate::undo::{Action, History};
+use crate::{print_players, Border, Player, Territory};
+
+// Classic two-player Risk adds a neutral third player that receives its share of
+// territories and armies but never acts.
+pub fn add_neutral_player(players: &mut Vec<Player>, rules: &RuleSet) {
+    if rules.neutral_in_two_player_games && players.len() == 2 {
+        println!("Two-player game, adding a neutral player that defends but never attacks.");
+        players.push(Player::neutral());
+    }
+}
+
+// The starting army quota depends on the number of players taking turns, the
+// neutral player receives the same quota.
+fn armies_per_player(territories: &UnGraph<Territory, Border>, players: &[Player], rules: &RuleSet) -> u32 {
+    let n_acting_players = players.iter().filter(|player| !player.is_neutral).count();
+    rules.starting_armies_for(n_acting_players, territories.node_count())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetupMode {
+    Random,
+    Draft,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Placement {
+    Manual,
+    Automatic,
+}
+
+// Deals or drafts the territories and places the starting armies. The setup mode
+// and placement are asked for unless they were given on the command line.
+pub fn assign_territories_and_armies_to_players(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    rules: &RuleSet,
+    setup_mode: Option<SetupMode>,
+    placement: Option<Placement>,
+    rng: &mut StdRng,
+    input: &mut Input) {
+    let setup_mode = setup_mode.unwrap_or_else(|| {
+        println!("How do you want to set up territories?");
+        let setup_mode = input.prompt_number(
+            "setup",
+            "Type 1 for a random deal, or 2 for a draft where players take turns claiming territories: ");
+        match setup_mode {
+            1 => SetupMode::Random,
+            2 => SetupMode::Draft,
+            _ => {
+                input.reject("Invalid input. Defaulting to a random deal.");
+                SetupMode::Random
+            }
+        }
+    });
+
+    let armies_per_player = armies_per_player(territories, players, rules);
+    match setup_mode {
+        SetupMode::Random => {
+            println!("Random deal selected.");
+            deal_territories_randomly(territories, players, rng);
+            match placement.unwrap_or_else(|| choose_placement(input)) {
+                Placement::Manual => {
+                    println!("Manual assignment mode selected, players will assign {} armies to territories.", armies_per_player);
+                    for player in players.iter_mut() {
+                        place_one_army_per_territory(player);
+                    }
+                    place_remaining_armies_in_turn_order(territories, players, armies_per_player, input);
+                }
+                Placement::Automatic => {
+                    println!("Automatic even assignment mode selected.");
+                    for player in players.iter_mut() {
+                        place_starting_armies_automatically(player, armies_per_player);
+                    }
+                }
+            }
+        },
+        SetupMode::Draft => {
+            println!("Draft selected.");
+            draft_territories(territories, players, rng, input);
+            // Drafted armies are placed by hand unless automatic placement was asked for.
+            if placement == Some(Placement::Automatic) {
+                for player in players.iter_mut() {
+                    place_starting_armies_automatically(player, armies_per_player);
+                }
+            } else {
+                place_remaining_armies_in_turn_order(territories, players, armies_per_player, input);
+            }
+        },
+    }
+
+    // Setup output is seen by everyone at once, so there is no viewer here.
+    println!("\nTerritories and armies have been assigned to players as follows:");
+    print_players(territories, players, None, rules.fog_of_war);
+}
+
+fn choose_placement(input: &mut Input) -> Placement {
+    println!("Do you want to manually assign armies, or automatically assign armies to all territories evenly?");
+    let manual_or_even_assignment = input.prompt_number("assign", "Type 1 for manual, or 2 for automatic even assignment: ");
+    match manual_or_even_assignment {
+        1 => Placement::Manual,
+        2 => Placement::Automatic,
+        _ => {
+            input.reject("Invalid input. Defaulting to automatic even assignment.");
+            Placement::Automatic
+        }
+    }
+}
+
+// Classic draft: players take turns claiming one unclaimed territory at a time,
+// placing one army on it, until every territory has been claimed. The neutral
+// player claims a random territory on its turn.
+fn draft_territories(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    rng: &mut StdRng,
+    input: &mut Input) {
+    let mut unclaimed_territory_indices: Vec<u32> = territories
+        .node_indices()
+        .map(|index| index.index() as u32)
+        .collect();
+
+    let mut player_index = 0;
+    while !unclaimed_territory_indices.is_empty() {
+        let player = &mut players[player_index];
+
+        let selected_index: u32 = if player.is_neutral {
+            *unclaimed_territory_indices.choose(rng).unwrap()
+        } else {
+            println!("\nPlayer: {}, unclaimed territories:", player.name);
+            for territory_index in unclaimed_territory_indices.iter() {
+                let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(*territory_index as usize)).unwrap().name;
+                println!("Territory index: {}, territory name: {}", territory_index, territory_name);
+            }
+
+            input.prompt_number("claim", "Choose a territory index to claim: ")
+        };
+
+        if let Some(position) = unclaimed_territory_indices.iter().position(|index| *index == selected_index) {
+            unclaimed_territory_indices.remove(position);
+            player.army_per_territory.insert(selected_index, 1);
+
+            let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(selected_index as usize)).unwrap().name;
+            println!("Player {} claimed {}.", player.name, territory_name);
+
+            player_index = (player_index + 1) % players.len();
+        } else {
+            input.reject("This territory is not available, please select again.");
+        }
+    }
+}
+
+// Parses a placement of the form "<territory index>" or
+// "<territory index> <number of armies>", where the number of armies defaults to 1.
+fn parse_placement(line: &str) -> Option<(u32, u32)> {
+    let mut parts = line.split_whitespace();
+    let territory_index = parts.next()?.parse().ok()?;
+    let n_armies = match parts.next() {
+        Some(n_armies) => n_armies.parse().ok()?,
+        None => 1,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((territory_index, n_armies))
+}
+
+// Players take turns placing armies on their own territories until each of them
+// has armies_per_player armies on the board. Each prompt places one or more
+// armies, and players who have already reached the quota are skipped, so the
+// phase only ends once every player has placed all their armies. The neutral
+// player never chooses, so its armies are spread evenly up front.
+fn place_remaining_armies_in_turn_order(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    armies_per_player: u32,
+    input: &mut Input) {
+    for player in players.iter_mut().filter(|player| player.is_neutral) {
+        place_starting_armies_automatically(player, armies_per_player);
+    }
+
+    let mut history = History::new();
+    let mut player_index = 0;
+    while players.iter().any(|player| player.total_armies() < armies_per_player) {
+        let player = &mut players[player_index];
+        let armies_left = armies_per_player.saturating_sub(player.total_armies());
+        if armies_left == 0 {
+            player_index = (player_index + 1) % players.len();
+            continue;
+        }
+
+        let mut sorted_territory_indices: Vec<u32> = player.army_per_territory.keys().cloned().collect();
+        sorted_territory_indices.sort();
+
+        println!("\nPlayer: {}, {} armies left to place, current territories:", player.name, armies_left);
+        for territory_index in sorted_territory_indices {
+            let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(territory_index as usize)).unwrap().name;
+            let armies = player.army_per_territory.get(&territory_index).unwrap();
+            println!("Territory index: {}, territory name: {}, Armies: {}", territory_index, territory_name, armies);
+        }
+
+        let placement = input.prompt(
+            "place",
+            "Choose a territory index to add armies to, optionally followed by the number of armies (e.g. \"5\" or \"5 3\"), or undo/redo: ");
+        if placement == "undo" || placement == "redo" {
+            let action = if placement == "undo" { history.undo(players) } else { history.redo(players) };
+            match action {
+                Some(Action::Placement { player_idx }) => {
+                    println!("Placement by {} {}.", players[player_idx].name, if placement == "undo" { "undone" } else { "redone" });
+                    // After an undo the same player places again, after a redo it is the next player's turn.
+                    player_index = if placement == "undo" { player_idx } else { (player_idx + 1) % players.len() };
+                }
+                _ => input.reject(&format!("There is nothing to {}.", placement)),
+            }
+            continue;
+        }
+
+        let (selected_index, mut n_armies) = match parse_placement(&placement) {
+            Some(placement) => placement,
+            None => {
+                input.reject("Please type a territory index, optionally followed by a number of armies.");
+                continue;
+            }
+        };
+
+        if n_armies == 0 {
+            input.reject("Cannot place zero armies, please select again.");
+            continue;
+        }
+        if n_armies > armies_left {
+            n_armies = armies_left;
+            println!("Requested too many armies, reducing to {}", n_armies);
+        }
+        if !player.army_per_territory.contains_key(&selected_index) {
+            input.reject("You do not own this territory, please select again.");
+            continue;
+        }
+
+        let before = players.to_vec();
+        let player = &mut players[player_index];
+        let armies = player.army_per_territory.get_mut(&selected_index).unwrap();
+        *armies += n_armies;
+
+        let territory_name = &territories.node_weight(petgraph::graph::NodeIndex::new(selected_index as usize)).unwrap().name;
+        println!("Player {} now has {} armies in {}.", player.name, *armies, territory_name);
+
+        if player.total_armies() >= armies_per_player {
+            println!("Player {} has assigned all their armies.", player.name);
+        }
+
+        history.record(Action::Placement { player_idx: player_index }, before, players);
+        player_index = (player_index + 1) % players.len();
+    }
+}
+
+// Every territory must hold at least one army, so this is placed for free out of
+// the player's quota before the rest is distributed.
+fn place_one_army_per_territory(player: &mut Player) {
+    for armies in player.army_per_territory.values_mut() {
+        *armies = 1;
+    }
+}
+
+// Places one army on each of the player's territories, then spreads the rest of
+// armies_per_player evenly over them. A player who owns more territories than
+// armies_per_player ends up with one army per territory.
+fn place_starting_armies_automatically(player: &mut Player, armies_per_player: u32) {
+    place_one_army_per_territory(player);
+
+    let mut sorted_territory_indices: Vec<u32> = player.army_per_territory.keys().cloned().collect();
+    sorted_territory_indices.sort();
+
+    let mut armies_left = armies_per_player.saturating_sub(player.total_armies());
+    for territory_index in sorted_territory_indices.iter().cycle() {
+        if armies_left == 0 {
+            break;
+        }
+        *player.army_per_territory.get_mut(territory_index).unwrap() += 1;
+        armies_left -= 1;
+    }
+}
+
+// Deals territories round-robin in a random order, then assigns the starting
+// armies either manually or automatically.
+fn deal_territories_randomly(
+    territories: &UnGraph<Territory, Border>,
+    players: &mut [Player],
+    rng: &mut StdRng) {
+    let mut territory_indices: Vec<u32> = territories
+        .node_indices()
+        .map(|index| index.index() as u32)
+        .collect();
+
+    // Randomly permute territory_indices so that we assign territories to players in
+    // a random manner.
+    territory_indices.shuffle(rng);
+
+    let mut player_index = 0;
+    for territory_index in territory_indices {
+        players[player_index].army_per_territory.insert(territory_index, 0); // Start with 0 armies
+        player_index = (player_index + 1) % players.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{setup_territories, Color, MAX_PLAYERS};
+    use rand::SeedableRng;
+
+    fn player_with_territories(name: &str, territory_indices: &[u32]) -> Player {
+        let mut player = Player::new(name.to_string(), Color::Red);
+        for territory_index in territory_indices {
+            player.army_per_territory.insert(*territory_index, 0);
+        }
+        player
+    }
+
+    #[test]
+    fn automatic_assignment_gives_every_player_exactly_their_quota() {
+        let territories = setup_territories();
+        let rules = RuleSet::classic();
+
+        for n_players in 1..=MAX_PLAYERS {
+            let mut players: Vec<Player> = (0..n_players)
+                .map(|i| Player::new(format!("Player {}", i), Color::Red))
+                .collect();
+            let mut input = Input::from_lines(&["2"]);
+            assign_territories_and_armies_to_players(
+                &territories,
+                &mut players,
+                &rules,
+                Some(SetupMode::Random),
+                None,
+                &mut StdRng::seed_from_u64(n_players as u64),
+                &mut input);
+
+            let armies_per_player = rules.starting_armies_for(n_players, territories.node_count());
+            for player in players.iter() {
+                assert_eq!(player.total_armies(), armies_per_player);
+                assert!(player.army_per_territory.values().all(|armies| *armies >= 1));
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_deals_the_same_territories() {
+        let territories = setup_territories();
+        let deal = |seed| {
+            let mut players = vec![
+                player_with_territories("Ann", &[]),
+                player_with_territories("Bo", &[]),
+            ];
+            deal_territories_randomly(&territories, &mut players, &mut StdRng::seed_from_u64(seed));
+            let mut territory_indices: Vec<u32> = players[0].army_per_territory.keys().cloned().collect();
+            territory_indices.sort();
+            territory_indices
+        };
+        assert_eq!(deal(7), deal(7));
+    }
+
+    #[test]
+    fn automatic_placement_keeps_one_army_per_territory_when_quota_is_too_small() {
+        let mut player = player_with_territories("Ann", &[0, 1, 2, 3]);
+        place_starting_armies_automatically(&mut player, 2);
+
+        assert_eq!(player.total_armies(), 4);
+        assert!(player.army_per_territory.values().all(|armies| *armies == 1));
+    }
+
+    #[test]
+    fn manual_placement_continues_after_first_player_reaches_quota() {
+        let territories = setup_territories();
+        let mut players = vec![
+            player_with_territories("Ann", &[0, 1]),
+            player_with_territories("Bo", &[2, 3, 4]),
+        ];
+        for player in players.iter_mut() {
+            place_one_army_per_territory(player);
+        }
+
+        // Bo needs one fewer army than Ann, so Bo finishes first and Ann must
+        // still be asked for their last army.
+        let mut input = Input::from_lines(&["0", "2", "1", "3", "0", "4", "1"]);
+        place_remaining_armies_in_turn_order(&territories, &mut players, 6, &mut input);
+
+        assert_eq!(players[0].total_armies(), 6);
+        assert_eq!(players[1].total_armies(), 6);
+        assert_eq!(players[0].army_per_territory[&0], 3);
+        assert_eq!(players[0].army_per_territory[&1], 3);
+    }
+
+    #[test]
+    fn manual_placement_accepts_multiple_armies_per_prompt() {
+        let territories = setup_territories();
+        let mut players = vec![
+            player_with_territories("Ann", &[0, 1]),
+            player_with_territories("Bo", &[2, 3, 4]),
+        ];
+        for player in players.iter_mut() {
+            place_one_army_per_territory(player);
+        }
+
+        // Ann asks for more armies than they have left, which is capped.
+        let mut input = Input::from_lines(&["0 10", "2 3"]);
+        place_remaining_armies_in_turn_order(&territories, &mut players, 6, &mut input);
+
+        assert_eq!(players[0].army_per_territory[&0], 5);
+        assert_eq!(players[0].total_armies(), 6);
+        assert_eq!(players[1].army_per_territory[&2], 4);
+        assert_eq!(players[1].total_armies(), 6);
+    }
+
+    #[test]
+    fn manual_placement_can_be_undone_and_redone() {
+        let territories = setup_territories();
+        let mut players = vec![
+            player_with_territories("Ann", &[0, 1]),
+            player_with_territories("Bo", &[2, 3, 4]),
+        ];
+        for player in players.iter_mut() {
+            place_one_army_per_territory(player);
+        }
+
+        // Ann takes back their placement and places on the other territory, then
+        // Bo undoes and redoes their placement.
+        let mut input = Input::from_lines(&["0 4", "undo", "1 4", "2 1", "undo", "redo", "3 2"]);
+        place_remaining_armies_in_turn_order(&territories, &mut players, 6, &mut input);
+
+        assert_eq!(players[0].army_per_territory[&0], 1);
+        assert_eq!(players[0].army_per_territory[&1], 5);
+        assert_eq!(players[1].army_per_territory[&2], 2);
+        assert_eq!(players[1].army_per_territory[&3], 3);
+    }
+
+    #[test]
+    fn manual_placement_asks_again_after_invalid_input() {
+        let territories = setup_territories();
+        let mut players = vec![player_with_territories("Ann", &[0, 1])];
+        place_one_army_per_territory(&mut players[0]);
+
+        let mut input = Input::from_lines(&["5", "0 0", "zero", "0 1 2", "1 2"]);
+        place_remaining_armies_in_turn_order(&territories, &mut players, 4, &mut input);
+
+        assert_eq!(players[0].army_per_territory[&0], 1);
+        assert_eq!(players[0].army_per_territory[&1], 3);
+    }
+
+    #[test]
+    fn neutral_player_receives_its_quota_without_prompting() {
+        let territories = setup_territories();
+        let mut players = vec![
+            player_with_territories("Ann", &[0, 1]),
+            player_with_territories("Bo", &[2, 3]),
+            Player::neutral(),
+        ];
+        players[2].army_per_territory.insert(4, 0);
+        players[2].army_per_territory.insert(5, 0);
+        for player in players.iter_mut() {
+            place_one_army_per_territory(player);
+        }
+
+        let mut input = Input::from_lines(&["0 3", "2 3"]);
+        place_remaining_armies_in_turn_order(&territories, &mut players, 5, &mut input);
+
+        for player in players.iter() {
+            assert_eq!(player.total_armies(), 5);
+        }
+        assert_eq!(players[2].army_per_territory[&4], 3);
+        assert_eq!(players[2].army_per_territory[&5], 2);
+    }
+
+    #[test]
+    fn neutral_player_is_only_added_to_two_player_games() {
+        let territories = setup_territories();
+        let rules = RuleSet::classic();
+
+        let mut players = vec![player_with_territories("Ann", &[]), player_with_territories("Bo", &[])];
+        add_neutral_player(&mut players, &rules);
+        assert_eq!(players.len(), 3);
+        assert!(players[2].is_neutral);
+        assert_eq!(armies_per_player(&territories, &players, &rules), rules.starting_armies_for(2, 16));
+
+        let mut players = vec![
+            player_with_territories("Ann", &[]),
+            player_with_territories("Bo", &[]),
+            player_with_territories("Cy", &[]),
+        ];
+        add_neutral_player(&mut players, &rules);
+        assert_eq!(players.len(), 3);
+    }
+
+    #[test]
+    fn draft_gives_every_player_exactly_their_quota() {
+        let territories = setup_territories();
+        let rules = RuleSet::classic();
+        let mut players = vec![
+            player_with_territories("Ann", &[]),
+            player_with_territories("Bo", &[]),
+        ];
+
+        // Ann claims the even territories and Bo the odd ones, then each places
+        // the rest of their armies in a single prompt.
+        let mut lines: Vec<String> = (0..territories.node_count()).map(|i| i.to_string()).collect();
+        lines.push("0 100".to_string());
+        lines.push("1 100".to_string());
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+        let mut input = Input::from_lines(&lines);
+
+        let armies_per_player = rules.starting_armies_for(players.len(), territories.node_count());
+        draft_territories(&territories, &mut players, &mut StdRng::seed_from_u64(0), &mut input);
+        place_remaining_armies_in_turn_order(&territories, &mut players, armies_per_player, &mut input);
+
+        assert_eq!(players[0].army_per_territory.len(), 8);
+        assert!(players[0].army_per_territory.keys().all(|index| index % 2 == 0));
+        for player in players.iter() {
+            assert_eq!(player.total_armies(), armies_per_player);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// Team play: players on the same team cannot attack each other, may fortify
+// through each other's territories, and win together.
+
+use crate::input::Input;
+use crate::Player;
+
+pub fn assign_teams(players: &mut [Player], input: &mut Input) {
+    let n_acting_players = players.iter().filter(|player| !player.is_neutral).count();
+    if n_acting_players < 3 {
+        return;
+    }
+
+    let play_in_teams = input.prompt_yes_no("teams", "Do you want to play in teams? (y/n): ");
+    if !matches!(play_in_teams.as_str(), "y" | "Y") {
+        return;
+    }
+
+    for player in players.iter_mut().filter(|player| !player.is_neutral) {
+        loop {
+            let team: u32 = input.prompt_number("teams", &format!("Enter team number for {}: ", player.name));
+            if team >= 1 {
+                player.team = Some(team);
+                break;
+            }
+            input.reject("Team numbers start at 1, please enter again.");
+        }
+    }
+
+    let first_team = players.iter().find_map(|player| player.team);
+    if players.iter().filter(|player| !player.is_neutral).all(|player| player.team == first_team) {
+        println!("All players are on the same team, playing without teams.");
+        for player in players.iter_mut() {
+            player.team = None;
+        }
+        return;
+    }
+
+    for player in players.iter().filter(|player| player.team.is_some()) {
+        println!("{} plays for team {}", player.name, player.team.unwrap());
+    }
+}
+
+// A player is always allied with themselves, and with everyone on their team.
+pub fn are_allies(players: &[Player], player_idx: usize, other_idx: usize) -> bool {
+    if player_idx == other_idx {
+        return true;
+    }
+    match (players[player_idx].team, players[other_idx].team) {
+        (Some(team), Some(other_team)) => team == other_team,
+        _ => false,
+    }
+}
+
+// Names the side a player plays for, e.g. "Player Ann" or "Team 1 (Ann, Cy)".
+pub fn describe_side(players: &[Player], player_idx: usize) -> String {
+    match players[player_idx].team {
+        Some(team) => {
+            let names: Vec<&str> = (0..players.len())
+                .filter(|other_idx| are_allies(players, player_idx, *other_idx))
+                .map(|other_idx| players[other_idx].name.as_str())
+                .collect();
+            format!("Team {} ({})", team, names.join(", "))
+        }
+        None => format!("Player {}", players[player_idx].name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    fn three_players_and_neutral() -> Vec<Player> {
+        vec![
+            Player::new("Ann".to_string(), Color::Red),
+            Player::new("Bo".to_string(), Color::Blue),
+            Player::new("Cy".to_string(), Color::Green),
+            Player::neutral(),
+        ]
+    }
+
+    #[test]
+    fn teams_are_assigned_to_every_player_but_the_neutral_one() {
+        // Team 0 is refused and asked for again.
+        let mut players = three_players_and_neutral();
+        let mut input = Input::from_lines(&["y", "1", "0", "2", "1"]);
+        assign_teams(&mut players, &mut input);
+        let teams: Vec<Option<u32>> = players.iter().map(|player| player.team).collect();
+        assert_eq!(teams, vec![Some(1), Some(2), Some(1), None]);
+        assert_eq!(players.iter().filter(|player| player.team == Some(1)).count(), 2);
+
+        // A single team is no team at all.
+        let mut players = three_players_and_neutral();
+        let mut input = Input::from_lines(&["y", "3", "3", "3"]);
+        assign_teams(&mut players, &mut input);
+        assert!(players.iter().all(|player| player.team.is_none()));
+
+        // Two players and the neutral one are not asked.
+        let mut players = three_players_and_neutral();
+        players.remove(2);
+        assign_teams(&mut players, &mut Input::from_lines(&[]));
+        assert!(players.iter().all(|player| player.team.is_none()));
+    }
+
+    #[test]
+    fn only_teammates_are_allies() {
+        let mut players = three_players_and_neutral();
+        assert!(are_allies(&players, 0, 0));
+        assert!(!are_allies(&players, 0, 2));
+
+        players[0].team = Some(1);
+        players[2].team = Some(1);
+        players[1].team = Some(2);
+        assert!(are_allies(&players, 0, 2));
+        assert!(are_allies(&players, 2, 0));
+        assert!(!are_allies(&players, 0, 1));
+        assert!(!are_allies(&players, 1, 3));
+        assert_eq!(describe_side(&players, 2), "Team 1 (Ann, Cy)");
+        assert_eq!(describe_side(&players, 3), "Player Neutral");
+    }
+}
@@ -0,0 +1,102 @@
+// Undo and redo for the choices a player makes without rolling dice, such as
+// placing armies, moving armies into a conquered territory, or fortifying. Every
+// action keeps a copy of the players before and after it. Rolling dice clears
+// the history, so dice outcomes are always final.
+
+use crate::Player;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    // A player placed armies during setup.
+    Placement { player_idx: usize },
+    // The attacker moved armies from one territory into the one they conquered,
+    // having to move at least min_armies.
+    ConquestMove { from: u32, to: u32, min_armies: u32 },
+    Fortify,
+}
+
+struct Entry {
+    action: Action,
+    before: Vec<Player>,
+    after: Vec<Player>,
+}
+
+#[derive(Default)]
+pub struct History {
+    done: Vec<Entry>,
+    undone: Vec<Entry>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    // Records an action, given the players as they were before it. A new action
+    // means the actions that were undone can no longer be redone.
+    pub fn record(&mut self, action: Action, before: Vec<Player>, players: &[Player]) {
+        self.done.push(Entry { action, before, after: players.to_vec() });
+        self.undone.clear();
+    }
+
+    // Restores the players to before the last action, and returns that action.
+    pub fn undo(&mut self, players: &mut [Player]) -> Option<Action> {
+        let entry = self.done.pop()?;
+        players.clone_from_slice(&entry.before);
+        let action = entry.action;
+        self.undone.push(entry);
+        Some(action)
+    }
+
+    // Restores the players to after the last undone action, and returns that action.
+    pub fn redo(&mut self, players: &mut [Player]) -> Option<Action> {
+        let entry = self.undone.pop()?;
+        players.clone_from_slice(&entry.after);
+        let action = entry.action;
+        self.done.push(entry);
+        Some(action)
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn undo_and_redo_restore_the_players() {
+        let mut players = vec![Player::new("Ann".to_string(), Color::Red)];
+        players[0].army_per_territory.insert(0, 1);
+        let mut history = History::new();
+
+        for armies in [2, 3] {
+            let before = players.clone();
+            players[0].army_per_territory.insert(0, armies);
+            history.record(Action::Placement { player_idx: 0 }, before, &players);
+        }
+
+        assert_eq!(history.undo(&mut players), Some(Action::Placement { player_idx: 0 }));
+        assert_eq!(players[0].army_per_territory[&0], 2);
+        assert_eq!(history.undo(&mut players), Some(Action::Placement { player_idx: 0 }));
+        assert_eq!(players[0].army_per_territory[&0], 1);
+        assert_eq!(history.undo(&mut players), None);
+
+        assert_eq!(history.redo(&mut players), Some(Action::Placement { player_idx: 0 }));
+        assert_eq!(players[0].army_per_territory[&0], 2);
+
+        // A new action replaces whatever was left to redo.
+        let before = players.clone();
+        players[0].army_per_territory.insert(0, 5);
+        history.record(Action::Fortify, before, &players);
+        assert_eq!(history.redo(&mut players), None);
+
+        history.clear();
+        assert_eq!(history.undo(&mut players), None);
+        assert_eq!(players[0].army_per_territory[&0], 5);
+    }
+}